@@ -1,3 +1,8 @@
+bits 32
+section .text
+global extra
+global extraFunctionThatReturnSomeValueAndCreateVariable
+global main
 extra:
 	push ebp
 	mov ebp, esp
@@ -33,4 +38,3 @@ main:
 	mov esp, ebp
 	pop ebp
 	ret
-
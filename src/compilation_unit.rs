@@ -1,195 +1,1166 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::process;
-use std::process::{Command, exit};
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::lexer::{Lexer, Token, TokenKind};
-use crate::parser::{Parser, Types, NodeKind, IntType, Node};
-
-pub fn compilation_unit(args: Vec<String>) {
-    // let file_name = &args[1];
-    // let output = &args[2];
-    let file_name = "main.su";
-    let output = "main.bin";
-    let mut file = File::open(file_name).unwrap();
-    let mut buffer = String::new();
-    file.read_to_string(&mut buffer).expect("file cannot open");
-
-    let mut lex = Lexer::new(buffer.as_str());
+use crate::parser::{common_type, int_type, BinaryOp, Parser, NodeKind, IntType, Node, Types, TypeTable, UnaryOp, Unit};
+use crate::diagnostics::{Diagnostic, Diagnostics, E_ASSEMBLER, E_LITERAL_OUT_OF_RANGE, E_MISMATCHED_TYPES, E_UNSUPPORTED, E_UNSUPPORTED_TYPE, NO_CODE};
+use crate::source::{FileId, SourceMap, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Asm,
+    Obj,
+    Bin
+}
+
+impl Emit {
+    pub fn from_name(name: &str) -> Result<Emit, String> {
+        match name {
+            "tokens" => Ok(Emit::Tokens),
+            "ast" => Ok(Emit::Ast),
+            "asm" => Ok(Emit::Asm),
+            "obj" => Ok(Emit::Obj),
+            "bin" => Ok(Emit::Bin),
+            _ => Err(format!("unknown emit stage '{}', expected tokens|ast|asm|obj|bin", name))
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::Asm => "asm",
+            Emit::Obj => "o",
+            Emit::Bin => "bin"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Json
+}
+
+impl ErrorFormat {
+    pub fn from_name(name: &str) -> Result<ErrorFormat, String> {
+        match name {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("unknown error format '{}', expected human|json", name))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub input: String,          // source path, "-" reads stdin
+    pub output: Option<String>, // defaults to the input path with the emit extension
+    pub emit: Emit,
+    pub error_format: ErrorFormat
+}
+
+impl Options {
+    pub fn new(input: &str, emit: Emit) -> Self {
+        Self {
+            input: input.to_string(),
+            output: None,
+            emit,
+            error_format: ErrorFormat::Human
+        }
+    }
+
+    pub fn output_path(&self) -> String {
+        match &self.output {
+            Some(path) => path.clone(),
+            None if self.input == "-" => format!("out.{}", self.emit.extension()),
+            None => Path::new(&self.input).with_extension(self.emit.extension()).to_string_lossy().to_string()
+        }
+    }
+}
+
+// result of a successful compilation, `diagnostics` only holds warnings
+#[derive(Debug)]
+pub struct Artifact {
+    pub emit: Emit,
+    pub bytes: Vec<u8>,
+    pub diagnostics: Diagnostics
+}
+
+// runs the pipeline on `file` up to `emit` and returns the bytes of that stage
+pub fn compile(sources: &SourceMap, file: FileId, emit: Emit) -> Result<Artifact, Diagnostics> {
+    let mut diagnostics = Diagnostics::new();
+    let source = match sources.get(file) {
+        Some(source) => source,
+        None => {
+            diagnostics.push(Diagnostic::error(NO_CODE, format!("no source file with id {}", file)));
+            return Err(diagnostics);
+        }
+    };
+    // lexing errors leave Error tokens behind, parsing still runs over them
+    // so one compile reports every problem in the file
+    let tokens = lex(&source.text, file, &mut diagnostics);
+    let bytes = if emit == Emit::Tokens {
+        if diagnostics.has_errors() {
+            return Err(diagnostics);
+        }
+        let mut listing = String::new();
+        for token in &tokens {
+            let at = source.location(token.span.start);
+            listing.push_str(format!("{}\t{}\t{:?}\n", at, token.kind, token.text).as_str());
+        }
+        listing.into_bytes()
+    } else {
+        let node = parse(&tokens, &mut diagnostics);
+        if diagnostics.has_errors() {
+            return Err(diagnostics);
+        }
+        if emit == Emit::Ast {
+            format!("{:#?}\n", node).into_bytes()
+        } else {
+            let asm = codegen(&node, &mut diagnostics);
+            if diagnostics.has_errors() {
+                return Err(diagnostics);
+            }
+            let assembled = match emit {
+                Emit::Obj => assemble(&asm, "elf32"),
+                Emit::Bin => assemble(&asm, "bin"),
+                _ => Ok(asm.into_bytes())
+            };
+            match assembled {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    diagnostics.push(e);
+                    return Err(diagnostics);
+                }
+            }
+        }
+    };
+    Ok(Artifact { emit, bytes, diagnostics })
+}
+
+// source text to tokens with whitespace and comments attached as trivia
+pub fn lex_lossless(source: &str, file: FileId, diagnostics: &mut Diagnostics) -> Vec<Token> {
+    Lexer::new(source, file, diagnostics).lossless()
+}
+
+// source text to tokens, whitespace and comments are dropped and the last token is always Eof
+pub fn lex(source: &str, file: FileId, diagnostics: &mut Diagnostics) -> Vec<Token> {
+    let mut lex = Lexer::new(source, file, diagnostics);
     let mut tokens: Vec<Token> = Vec::new();
     while let Some(c) = lex.tokenize() {
         if c.kind == TokenKind::Eof {
             tokens.push(c);
             break;
         }
-        if c.kind == TokenKind::WhiteSpace {
+        if c.is_trivia() {
             continue
         }
         tokens.push(c);
     }
+    tokens
+}
 
-    let mut parse = Parser::new(&tokens);
-    let node = parse.parsing_unit();
-    let mut asm = String::new();
-
-    let mut epilogue = String::new();
-    let mut content = String::new();
-    let mut stack_alloc = 16;
-    let mut stack_size_base = 0;
-    for i in 0..node.len() {
-        let nod = &node[i];
-        if nod.typ == Types::Function {
-            match &nod.kind {
-                NodeKind::Function(name, typ, param, body) => {
-                    // match &param.kind {
-                    //     NodeKind::Param(v) => println!("{:?}",v[0]),
-                    //     _ => {}
-                    // }
-
-                    asm.push_str(format!("{}:\n",name).as_str());
-                    match &body.kind {
-                        NodeKind::Scope(a) => {
-                            epilogue.clear();
-                            content.clear();
-                            stack_alloc = 16;
-                            stack_size_base = 0;
-                            epilogue.push_str("\tpush ebp\n\
-                                               \tmov ebp, esp\n");
-                            for i in 0..a.len() {
-                                match &a[i].kind {
-                                    NodeKind::Return(a) => {
-                                        let mut reg = String::new();
-                                        let mut verified;
-                                        match a.kind {
-                                            NodeKind::NumberLit(n) => {
-                                                verified = match typ {
-                                                    IntType::Int8   => match n {
-                                                        -0x80..=0x7f => {
-                                                            reg.push_str("al");
-                                                            n
-                                                        },
-                                                        _ => panic!("Value Not Fit into i8 . {} but range is -128 .. 127", n)
-                                                    }
-                                                    IntType::Uint8  => match n {
-                                                        0x00..=0xff => {
-                                                            reg.push_str("al");
-                                                            n
-                                                        }
-                                                        _ => panic!("Value Not Fit into u8 . {} but range is 0 .. 255", n)
-                                                    },
-                                                    IntType::Int16  => match n {
-                                                        -0x8000..=0x7fff => {
-                                                            reg.push_str("ax");
-                                                            n
-                                                        }
-                                                        _ => panic!("Value Not Fit into i16. {} but range is -32,768 .. 32,767", n)
-                                                    },
-                                                    IntType::Uint16 => match n {
-                                                        0x0000..=0xffff => {
-                                                            reg.push_str("ax");
-                                                            n
-                                                        }
-                                                        _ => panic!("Value Not Fit into u16. {} but range is 0 .. 65,535", n)
-                                                    },
-                                                    IntType::Int32  => match n {
-                                                        -0x80000000..=0x7fffffff => {
-                                                            reg.push_str("eax");
-                                                            n
-                                                        }
-                                                        _ => panic!("Value Not Fit into i32. {} but range is -2,147,483,648 .. 2,147,483,647", n)
-                                                    },
-                                                    IntType::Uint32 => match n {
-                                                        0x00000000..=0xffffffff => {
-                                                            reg.push_str("eax");
-                                                            n
-                                                        }
-                                                        _ => panic!("Value Not Fit Into u32. {} but range is 0 .. 2,147,483,647", n)
-                                                    },
-                                                    _ => panic!("Not Supported Type: {:?}", typ)
-                                                };
-                                            },
-                                            _ => todo!()
-                                        }
-                                        content.push_str(format!("\tmov {}, {}\n",reg, verified).as_str());
-                                    },
-                                    NodeKind::Variable(v) => {
-                                        let type_ = match v.types {
-                                            IntType::Int8  => match v.content {
-                                                -0x80..=0x7f => "byte",
-                                                _ => panic!("Value of '{}' Not Fit into i8. {} but range is -128 .. 127", v.name, v.content)
-                                            }
-                                            IntType::Uint8 => match v.content {
-                                                0x00..=0xff => "byte",
-                                                _ => panic!("Value of '{}' Not Fit into u8. {} but range is 0 .. 255", v.name, v.content)
-                                            },
-                                            IntType::Int16 => match v.content {
-                                                -0x8000..=0x7fff => "word",
-                                                _ => panic!("Value of '{}' Not Fit into i16. {} but range is -32,768 .. 32,767", v.name, v.content)
-                                            },
-                                            IntType::Uint16=> match v.content {
-                                                0x0000..=0xffff => "word",
-                                                _ => panic!("Value of '{}' Not Fit into u16. {} but range is 0 .. 65,535", v.name, v.content)
-                                            },
-                                            IntType::Int32 => match v.content {
-                                                -0x80000000..=0x7fffffff => "dword",
-                                                _ => panic!("Value of '{}' Not Fit into i32. {} but range is -2,147,483,648 .. 2,147,483,647", v.name, v.content)
-                                            },
-                                            IntType::Uint32=> match v.content {
-                                                0x00000000..=0xffffffff => "dword",
-                                                _ => panic!("Value of '{}' Not Fit Into u32. {} but range is 0 .. 2,147,483,647", v.name, v.content)
-                                            },
-                                            _ => panic!("Not Supported Type {:?}", v.types)
-                                        };
-                                        let stack_size = match type_ {
-                                            "byte" => {
-                                                1
-                                            },
-                                            "word" => {
-                                                2
-                                            },
-                                            "dword" => {
-                                                4
-                                            },
-                                            _ => panic!("Not Supported Size {}", type_)
-                                        };
-                                        let size = stack_size_base;
-                                        stack_size_base = (size + stack_size - 1) & !(stack_size - 1);
-                                        stack_size_base += stack_size;
-                                        let base = 16;
-                                        stack_alloc = (stack_size_base + base - 1) & !(base - 1);
-                                        let template = format!("\tmov {} [ebp-{}], {}\n", type_, stack_size_base, v.content);
-                                        content.push_str(&template);
-                                    }
-                                    _ => todo!()
-                                };
-                            }
-                            epilogue.push_str(format!("\tsub esp, {}\n", stack_alloc).as_str());
-                            asm.push_str(epilogue.as_str());
-                            asm.push_str(content.as_str());
-                            let template = "\tmov esp, ebp\n\
-                                            \tpop ebp\n\
-                                            \tret\n";
-                            asm.push_str(template)
+// parses `tokens` into items, errors go to `diagnostics`
+pub fn parse(tokens: &[Token], diagnostics: &mut Diagnostics) -> Unit {
+    Parser::new(tokens, diagnostics).parsing_unit()
+}
+
+// numbers the temporary directories of concurrent `assemble` calls in one process
+static ASSEMBLED: AtomicUsize = AtomicUsize::new(0);
+
+// hands the generated assembly to nasm, `format` is passed through as `-f`,
+// every run works in its own private directory which is removed afterwards
+fn assemble(asm: &str, format: &str) -> Result<Vec<u8>, Diagnostic> {
+    let run = ASSEMBLED.fetch_add(1, Ordering::Relaxed);
+    let dir = env::temp_dir().join(format!("suco-{}-{}", process::id(), run));
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(&dir)
+        .map_err(|e| Diagnostic::error(E_ASSEMBLER, format!("cannot create {}: {}", dir.display(), e)))?;
+    let result = assemble_in(&dir, asm, format);
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+fn assemble_in(dir: &Path, asm: &str, format: &str) -> Result<Vec<u8>, Diagnostic> {
+    let failed = |message: String| Diagnostic::error(E_ASSEMBLER, message);
+    let asm_path = dir.join("input.asm");
+    let out_path = dir.join("output");
+    OpenOptions::new().write(true).create_new(true).open(&asm_path)
+        .and_then(|mut file| file.write_all(asm.as_bytes()))
+        .map_err(|e| failed(format!("cannot write {}: {}", asm_path.display(), e)))?;
+    let output = Command::new("nasm")
+        .arg("-f").arg(format)
+        .arg("-o").arg(&out_path)
+        .arg(&asm_path)
+        .output();
+    match output {
+        Ok(o) if o.status.success() => fs::read(&out_path).map_err(|e| failed(format!("cannot read {}: {}", out_path.display(), e))),
+        Ok(o) => {
+            // nasm reports against the temporary file, its text is kept as is
+            let mut e = failed(format!("nasm failed with {}", o.status));
+            for line in String::from_utf8_lossy(&o.stderr).lines().filter(|l| !l.trim().is_empty()) {
+                e = e.with_note(line.to_string());
+            }
+            Err(e)
+        },
+        Err(e) => Err(failed(format!("cannot run nasm: {}", e)))
+    }
+}
+
+// inclusive value range of the integer types codegen can store
+fn int_range(typ: IntType) -> Option<(i64, i64)> {
+    match typ {
+        IntType::Int8   => Some((-0x80, 0x7f)),
+        IntType::Uint8  => Some((0x00, 0xff)),
+        IntType::Int16  => Some((-0x8000, 0x7fff)),
+        IntType::Uint16 => Some((0x0000, 0xffff)),
+        IntType::Int32  => Some((-0x80000000, 0x7fffffff)),
+        IntType::Uint32 => Some((0x00000000, 0xffffffff)),
+        _ => None
+    }
+}
+
+// checks that `n` fits into `typ`, reporting an error at `span` when it does not
+fn check_literal(n: i64, typ: IntType, span: Span, diagnostics: &mut Diagnostics) -> bool {
+    match int_range(typ) {
+        Some((min, max)) if n < min || n > max => {
+            diagnostics.push(Diagnostic::error(E_LITERAL_OUT_OF_RANGE, format!("literal out of range for `{}`", typ))
+                .with_label(span, format!("the literal `{}` does not fit into the type `{}`", n, typ))
+                .with_note(format!("the range of `{}` is {} .. {}", typ, min, max)));
+            false
+        }
+        Some(_) => true,
+        None => {
+            diagnostics.push(Diagnostic::error(E_UNSUPPORTED_TYPE, format!("type `{}` is not supported by the x86 backend", typ))
+                .with_label(span, format!("this needs a value of type `{}`", typ)));
+            false
+        }
+    }
+}
+
+fn unsupported(span: Span, what: &str) -> Diagnostic {
+    Diagnostic::error(E_UNSUPPORTED, format!("this {} is not supported by the x86 backend", what))
+        .with_label(span, "not supported yet".to_string())
+}
+
+// immediate operand for a constant of type `typ`, None after reporting an error
+fn immediate(value: &Node, typ: IntType, diagnostics: &mut Diagnostics) -> Option<String> {
+    match value.kind {
+        NodeKind::NumberLit(n, _) => check_literal(n, typ, value.span, diagnostics).then(|| n.to_string()),
+        NodeKind::String(index) => Some(format!("str.{}", index)),
+        _ => {
+            diagnostics.push(unsupported(value.span, "value"));
+            None
+        }
+    }
+}
+
+// accumulator register and operand size keyword for a value of `size` bytes
+fn register(size: u32) -> &'static str {
+    match size {
+        1 => "al",
+        2 => "ax",
+        _ => "eax"
+    }
+}
+
+fn width(size: u32) -> &'static str {
+    match size {
+        1 => "byte",
+        2 => "word",
+        _ => "dword"
+    }
+}
+
+// widens the low part of eax back to 32 bits, sign or zero filled by `typ`
+fn extend(typ: IntType, asm: &mut String) {
+    match (typ.size(), typ.is_signed()) {
+        (1, true) => asm.push_str("\tmovsx eax, al\n"),
+        (1, false) => asm.push_str("\tmovzx eax, al\n"),
+        (2, true) => asm.push_str("\tmovsx eax, ax\n"),
+        (2, false) => asm.push_str("\tmovzx eax, ax\n"),
+        _ => {}
+    }
+}
+
+// converts the value in `reg` (eax or ecx), kept extended as `from`, to
+// `to` so both operands of an operation agree on the narrower bits
+fn convert(from: IntType, to: IntType, reg: &str, asm: &mut String) {
+    if from == to {
+        return;
+    }
+    let low = &reg[1..2];
+    let op = if to.is_signed() { "movsx" } else { "movzx" };
+    match to.size() {
+        1 => asm.push_str(format!("\t{} {}, {}l\n", op, reg, low).as_str()),
+        2 => asm.push_str(format!("\t{} {}, {}x\n", op, reg, low).as_str()),
+        _ => {}
+    }
+}
+
+// constants can be used as an operand directly
+fn is_constant(node: &Node) -> bool {
+    matches!(node.kind, NodeKind::NumberLit(..) | NodeKind::String(_))
+}
+
+// `[base+offset]`, without a zero offset
+fn displaced(base: &str, offset: u32) -> String {
+    if offset == 0 {
+        format!("[{}]", base)
+    } else {
+        format!("[{}+{}]", base, offset)
+    }
+}
+
+// operand `at` bytes into a store destination, the stack slot at `offset`
+// or, for None, the address saved at [esp] which is reloaded into edx
+fn destination(dest: Option<i32>, at: u32, content: &mut String) -> String {
+    match dest {
+        Some(offset) => address(offset + at as i32),
+        None => {
+            content.push_str("\tmov edx, [esp]\n");
+            displaced("edx", at)
+        }
+    }
+}
+
+// ecx sized for a copy chunk of `size` bytes
+fn scratch(size: u32) -> &'static str {
+    match size {
+        1 => "cl",
+        2 => "cx",
+        _ => "ecx"
+    }
+}
+
+// a named value on the stack, `offset` is relative to ebp, negative for
+// locals and positive for parameters
+struct Local {
+    name: String,
+    typ: IntType,
+    offset: i32
+}
+
+fn address(offset: i32) -> String {
+    if offset < 0 {
+        format!("[ebp-{}]", -offset)
+    } else {
+        format!("[ebp+{}]", offset)
+    }
+}
+
+// reads a value of `typ` at `at` into eax, extended to 32 bits
+fn load(typ: IntType, at: &str) -> String {
+    match (typ.size(), typ.is_signed()) {
+        (1, true) => format!("\tmovsx eax, byte {}\n", at),
+        (1, false) => format!("\tmovzx eax, byte {}\n", at),
+        (2, true) => format!("\tmovsx eax, word {}\n", at),
+        (2, false) => format!("\tmovzx eax, word {}\n", at),
+        _ => format!("\tmov eax, dword {}\n", at)
+    }
+}
+
+// lossless implicit conversions, the same type or a wider one that keeps
+// every value of `from`
+fn widens(from: IntType, to: IntType) -> bool {
+    from == to || (from.size() < to.size() && (to.is_signed() || !from.is_signed()))
+}
+
+// size of what a pointer points to, 0 for integers
+fn element_size(typ: &Types, types: &TypeTable) -> u32 {
+    typ.pointee().map_or(0, |to| to.detect(types))
+}
+
+// type both sides of a comparison are converted to
+fn compared(lhs: &Node, rhs: &Node) -> IntType {
+    common_type(int_type(&lhs.typ), int_type(&rhs.typ))
+}
+
+// operands are compared as 32-bit values, only `u32` needs unsigned jumps
+// since every narrower type is extended into range of `i32`
+fn compare_signed(lhs: &Node, rhs: &Node) -> bool {
+    let typ = compared(lhs, rhs);
+    typ.is_signed() || typ.size() < 4
+}
+
+// condition code suffix for `jcc` and `setcc`
+fn condition_code(op: BinaryOp, signed: bool) -> &'static str {
+    match (op, signed) {
+        (BinaryOp::Eq, _) => "e",
+        (BinaryOp::Ne, _) => "ne",
+        (BinaryOp::Lt, true) => "l",
+        (BinaryOp::Lt, false) => "b",
+        (BinaryOp::Le, true) => "le",
+        (BinaryOp::Le, false) => "be",
+        (BinaryOp::Gt, true) => "g",
+        (BinaryOp::Gt, false) => "a",
+        (BinaryOp::Ge, true) => "ge",
+        (BinaryOp::Ge, false) => "ae",
+        _ => unreachable!("not a comparison")
+    }
+}
+
+// x86 backend state, `locals` and `stack_size` belong to the function being generated
+struct Codegen<'a> {
+    diagnostics: &'a mut Diagnostics,
+    types: &'a TypeTable,    // struct layouts of the unit
+    locals: Vec<Local>,
+    stack_size: u32,
+    labels: usize,
+    loops: Vec<(Option<String>, String, String)>,    // label, `continue` and `break` targets
+    function: String,
+    tables: Vec<String>    // switch jump tables, emitted with the read only data
+}
+
+impl<'a> Codegen<'a> {
+    fn new(diagnostics: &'a mut Diagnostics, types: &'a TypeTable) -> Self {
+        Self {
+            diagnostics,
+            types,
+            locals: Vec::new(),
+            stack_size: 0,
+            labels: 0,
+            loops: Vec::new(),
+            function: String::new(),
+            tables: Vec::new()
+        }
+    }
+
+    fn function(&mut self, name: &str, typ: IntType, param: &Node, body: &Node, asm: &mut String) {
+        asm.push_str(format!("{}:\n", name).as_str());
+        self.function = name.to_string();
+        let statements = match &body.kind {
+            NodeKind::Scope(statements) => statements,
+            _ => return
+        };
+        self.locals.clear();
+        self.stack_size = 0;
+        // cdecl, the caller pushed every argument as a dword above the return address
+        if let NodeKind::Param(params) = &param.kind {
+            for (i, p) in params.iter().enumerate() {
+                self.locals.push(Local { name: p.name.clone(), typ: int_type(&p.types), offset: 8 + 4 * i as i32 });
+            }
+        }
+        let mut content = String::new();
+        for (i, stat) in statements.iter().enumerate() {
+            // a return at the very end falls through into the epilogue
+            match &stat.kind {
+                NodeKind::Return(value) if i + 1 == statements.len() => self.ret(value.as_deref(), typ, &mut content),
+                _ => self.statement(stat, typ, &mut content)
+            }
+        }
+        if content.contains("\tjmp .return\n") {
+            content.push_str(".return:\n");
+        }
+        let stack_alloc = ((self.stack_size + 15) & !15).max(16);
+        asm.push_str("\tpush ebp\n\
+                      \tmov ebp, esp\n");
+        asm.push_str(format!("\tsub esp, {}\n", stack_alloc).as_str());
+        asm.push_str(content.as_str());
+        let template = "\tmov esp, ebp\n\
+                        \tpop ebp\n\
+                        \tret\n";
+        asm.push_str(template)
+    }
+
+    fn statement(&mut self, stat: &Node, typ: IntType, content: &mut String) {
+        match &stat.kind {
+            NodeKind::Return(value) => {
+                self.ret(value.as_deref(), typ, content);
+                content.push_str("\tjmp .return\n");
+            },
+            NodeKind::If(cond, then, otherwise) => {
+                let skip = self.label("else");
+                self.branch(cond, &skip, false, content);
+                self.block(then, typ, content);
+                match otherwise {
+                    Some(otherwise) => {
+                        let end = self.label("endif");
+                        content.push_str(format!("\tjmp {}\n{}:\n", end, skip).as_str());
+                        self.statement(otherwise, typ, content);
+                        content.push_str(format!("{}:\n", end).as_str());
+                    }
+                    None => content.push_str(format!("{}:\n", skip).as_str())
+                }
+            },
+            NodeKind::Scope(_) => self.block(stat, typ, content),
+            NodeKind::Variable(v) => {
+                let offset = self.allocate(v.types.detect(self.types), v.types.align(self.types));
+                self.store_value(Some(offset), 0, &v.types, &v.content, content);
+                self.locals.push(Local { name: v.name.clone(), typ: int_type(&v.types), offset });
+            }
+            NodeKind::Assign(op, target, value) => {
+                // `x op= v` is evaluated as `x = x op v`
+                let value = match op {
+                    Some(op) => Node {
+                        kind: NodeKind::Binary(*op, target.clone(), value.clone()),
+                        typ: target.typ.clone(),
+                        span: stat.span
+                    },
+                    None => (**value).clone()
+                };
+                match &target.kind {
+                    NodeKind::Identifier(name) => match self.lookup(name) {
+                        Some(local) => {
+                            let offset = local.offset;
+                            self.store_value(Some(offset), 0, &target.typ, &value, content);
                         },
-                        _ => {}
+                        None => self.diagnostics.push(unsupported(target.span, "name"))
+                    },
+                    // the address of an aggregate place stays on the stack while it is filled
+                    _ if target.typ.is_aggregate() => {
+                        if self.address_of(target, content) {
+                            content.push_str("\tpush eax\n");
+                            self.store_value(None, 0, &target.typ, &value, content);
+                            content.push_str("\tadd esp, 4\n");
+                        }
+                    },
+                    _ => self.store_at(target, int_type(&target.typ), &value, content)
+                }
+            },
+            NodeKind::While(label, cond, body) => {
+                let (head, end) = (self.label("while"), self.label("wend"));
+                content.push_str(format!("{}:\n", head).as_str());
+                self.branch(cond, &end, false, content);
+                self.loop_body(label, &head, &end, body, typ, content);
+                content.push_str(format!("\tjmp {}\n{}:\n", head, end).as_str());
+            },
+            NodeKind::Loop(label, body) => {
+                let (head, end) = (self.label("loop"), self.label("lend"));
+                content.push_str(format!("{}:\n", head).as_str());
+                self.loop_body(label, &head, &end, body, typ, content);
+                content.push_str(format!("\tjmp {}\n{}:\n", head, end).as_str());
+            },
+            NodeKind::For(label, counter, to, body) => {
+                // the bound is evaluated once into a hidden dword slot
+                let tp = int_type(&counter.types);
+                let offset = self.allocate(tp.size(), tp.size());
+                self.store(offset, tp, &counter.content, content);
+                // the counter has to be able to reach the bound, or it wraps
+                // around and the loop never ends
+                match to.kind {
+                    NodeKind::NumberLit(n, _) => {
+                        check_literal(n, tp, to.span, self.diagnostics);
+                    },
+                    _ if !widens(int_type(&to.typ), tp) => {
+                        self.diagnostics.push(Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                            .with_label(to.span, format!("a `{}` bound does not fit into the `{}` counter", to.typ, tp))
+                            .with_note(format!("annotate the counter with a type `{}` converts to", to.typ)));
+                    },
+                    _ => {}
+                }
+                let bound = self.allocate(4, 4);
+                let wide = if tp.is_signed() { IntType::Int32 } else { IntType::Uint32 };
+                self.store(bound, wide, to, content);
+                let (head, next, end) = (self.label("for"), self.label("fnext"), self.label("fend"));
+                let local = Local { name: counter.name.clone(), typ: tp, offset };
+                let signed = tp.is_signed() || tp.size() < 4;
+                content.push_str(format!("{}:\n{}\tcmp eax, dword {}\n\tj{} {}\n",
+                                         head, load(tp, &address(offset)), address(bound), condition_code(BinaryOp::Ge, signed), end).as_str());
+                self.locals.push(local);
+                self.loop_body(label, &next, &end, body, typ, content);
+                let local = self.locals.pop().expect("counter pushed above");
+                content.push_str(format!("{}:\n{}\tadd eax, 1\n\tmov {} {}, {}\n\tjmp {}\n{}:\n",
+                                         next, load(local.typ, &address(offset)), width(local.typ.size()), address(offset), register(local.typ.size()), head, end).as_str());
+            },
+            NodeKind::Switch(value, cases, default) => {
+                if !self.expression(value, content) {
+                    return;
+                }
+                let scrutinee = int_type(&value.typ);
+                let end = self.label("send");
+                let fallback = if default.is_some() { self.label("sdefault") } else { end.clone() };
+                let mut arms: Vec<(i64, String)> = Vec::new();
+                let mut labels = Vec::new();
+                for case in cases {
+                    let label = self.label("case");
+                    for value in &case.values {
+                        if let NodeKind::NumberLit(n, _) = value.kind {
+                            if check_literal(n, scrutinee, value.span, self.diagnostics) {
+                                arms.push((n, label.clone()));
+                            }
+                        }
+                    }
+                    labels.push(label);
+                }
+                arms.sort_by_key(|(n, _)| *n);
+                match (arms.first(), arms.last()) {
+                    // dense cases index a table of case addresses, bounds checked
+                    // with one unsigned compare after rebasing on the smallest value
+                    (Some((min, _)), Some((max, _))) if arms.len() >= 4 && max - min < 3 * arms.len() as i64 => {
+                        let table = format!("switch.{}", self.labels);
+                        let mut entries = Vec::new();
+                        for n in *min..=*max {
+                            let target = arms.iter().find(|(m, _)| *m == n).map_or(&fallback, |(_, label)| label);
+                            entries.push(format!("{}{}", self.function, target));
+                        }
+                        if *min != 0 {
+                            content.push_str(format!("\tsub eax, {}\n", min).as_str());
+                        }
+                        content.push_str(format!("\tcmp eax, {}\n\
+                                                  \tja {}\n\
+                                                  \tjmp [{} + eax*4]\n", max - min, fallback, table).as_str());
+                        self.tables.push(format!("{}:\n\tdd {}\n", table, entries.join(", ")));
+                    },
+                    _ => {
+                        for (n, label) in &arms {
+                            content.push_str(format!("\tcmp eax, {}\n\
+                                                      \tje {}\n", n, label).as_str());
+                        }
+                        content.push_str(format!("\tjmp {}\n", fallback).as_str());
+                    }
+                }
+                for (case, label) in cases.iter().zip(&labels) {
+                    content.push_str(format!("{}:\n", label).as_str());
+                    self.block(&case.body, typ, content);
+                    content.push_str(format!("\tjmp {}\n", end).as_str());
+                }
+                if let Some(default) = default {
+                    content.push_str(format!("{}:\n", fallback).as_str());
+                    self.block(default, typ, content);
+                }
+                content.push_str(format!("{}:\n", end).as_str());
+            },
+            // a call on its own, whatever it returns is dropped
+            NodeKind::Call(..) => {
+                self.call(stat, content);
+            },
+            NodeKind::Break(label) | NodeKind::Continue(label) => {
+                let target = self.loops.iter().rev()
+                    .find(|(name, _, _)| label.is_none() || name == label)
+                    .map(|(_, next, end)| if matches!(stat.kind, NodeKind::Break(_)) { end.clone() } else { next.clone() });
+                match target {
+                    Some(target) => content.push_str(format!("\tjmp {}\n", target).as_str()),
+                    None => self.diagnostics.push(unsupported(stat.span, "jump"))
+                }
+            },
+            _ => self.diagnostics.push(unsupported(stat.span, "statement"))
+        }
+    }
+
+    // evaluates `value` into the slot at `offset`, constants are stored directly
+    fn store(&mut self, offset: i32, typ: IntType, value: &Node, content: &mut String) {
+        let value = if is_constant(value) {
+            immediate(value, typ, self.diagnostics)
+        } else {
+            self.expression(value, content).then(|| register(typ.size()).to_string())
+        };
+        if let Some(value) = value {
+            content.push_str(format!("\tmov {} {}, {}\n", width(typ.size()), address(offset), value).as_str());
+        }
+    }
+
+    // writes `value` of type `typ` to `at` bytes into the destination, the
+    // slot at `offset` for Some or the address saved at [esp] for None,
+    // arrays and structs are filled member by member or copied from a place
+    fn store_value(&mut self, dest: Option<i32>, at: u32, typ: &Types, value: &Node, content: &mut String) {
+        let types = self.types;
+        match (&value.kind, typ) {
+            (NodeKind::ArrayLit(values), Types::Array(element, _)) => {
+                let size = element.detect(types);
+                for (i, value) in values.iter().enumerate() {
+                    self.store_value(dest, at + i as u32 * size, element, value, content);
+                }
+            },
+            (NodeKind::StructLit(values), Types::Struct(name)) => {
+                let layout = match types.get(name) {
+                    Some(layout) => layout,
+                    None => return self.diagnostics.push(unsupported(value.span, "struct"))
+                };
+                for (name, value) in values {
+                    if let Some(field) = layout.field(name) {
+                        self.store_value(dest, at + field.offset, &field.types, value, content);
                     }
-                },
-                _ => {}
+                }
+            },
+            // aggregate elements are unrolled, the loop only fills scalars
+            (NodeKind::ArrayRepeat(value, count), Types::Array(element, _)) if element.is_aggregate() => {
+                let size = element.detect(types);
+                for i in 0..*count {
+                    self.store_value(dest, at + i * size, element, value, content);
+                }
+            },
+            (NodeKind::ArrayRepeat(value, count), Types::Array(element, _)) => {
+                let size = element.detect(types);
+                let source = if is_constant(value) {
+                    immediate(value, int_type(element), self.diagnostics)
+                } else {
+                    self.expression(value, content).then(|| register(size).to_string())
+                };
+                let source = match source {
+                    Some(source) => source,
+                    None => return
+                };
+                let slot = destination(dest, at, content);
+                let fill = self.label("fill");
+                content.push_str(format!("\tlea edx, {}\n\
+                                          \tmov ecx, {}\n\
+                                          {}:\n\
+                                          \tmov {} [edx], {}\n\
+                                          \tadd edx, {}\n\
+                                          \tsub ecx, 1\n\
+                                          \tjnz {}\n", slot, count, fill, width(size), source, size, fill).as_str());
+            },
+            _ if typ.is_aggregate() && value.is_place() => {
+                if !self.address_of(value, content) {
+                    return;
+                }
+                let slot = destination(dest, at, content);
+                if slot != "[edx]" {
+                    content.push_str(format!("\tlea edx, {}\n", slot).as_str());
+                }
+                self.copy(typ.detect(types), content);
+            },
+            _ if typ.is_aggregate() => self.diagnostics.push(unsupported(value.span, "aggregate value")),
+            _ => {
+                let int = int_type(typ);
+                if let Some(offset) = dest {
+                    return self.store(offset + at as i32, int, value, content);
+                }
+                let source = if is_constant(value) {
+                    immediate(value, int, self.diagnostics)
+                } else {
+                    self.expression(value, content).then(|| register(int.size()).to_string())
+                };
+                if let Some(source) = source {
+                    let slot = destination(dest, at, content);
+                    content.push_str(format!("\tmov {} {}, {}\n", width(int.size()), slot, source).as_str());
+                }
+            }
+        }
+    }
+
+    // copies `size` bytes from [eax] to [edx], small copies are unrolled
+    fn copy(&mut self, size: u32, content: &mut String) {
+        if size > 64 {
+            content.push_str(format!("\tpush esi\n\
+                                      \tpush edi\n\
+                                      \tmov esi, eax\n\
+                                      \tmov edi, edx\n\
+                                      \tmov ecx, {}\n\
+                                      \trep movsb\n\
+                                      \tpop edi\n\
+                                      \tpop esi\n", size).as_str());
+            return;
+        }
+        let mut done = 0;
+        for step in [4, 2, 1] {
+            while size - done >= step {
+                content.push_str(format!("\tmov {}, {}\n\
+                                          \tmov {} {}, {}\n", scratch(step), displaced("eax", done), width(step), displaced("edx", done), scratch(step)).as_str());
+                done += step;
+            }
+        }
+    }
+
+    // writes `value` to the place `target` names, through its address
+    fn store_at(&mut self, target: &Node, typ: IntType, value: &Node, content: &mut String) {
+        if is_constant(value) {
+            let value = match immediate(value, typ, self.diagnostics) {
+                Some(value) => value,
+                None => return
             };
+            if self.address_of(target, content) {
+                content.push_str(format!("\tmov {} [eax], {}\n", width(typ.size()), value).as_str());
+            }
+            return;
+        }
+        // the value waits on the stack while the address is computed
+        if !self.expression(value, content) {
+            return;
+        }
+        content.push_str("\tpush eax\n");
+        if self.address_of(target, content) {
+            content.push_str(format!("\tmov ecx, eax\n\
+                                      \tpop eax\n\
+                                      \tmov {} [ecx], {}\n", width(typ.size()), register(typ.size())).as_str());
+        }
+    }
+
+    // `continue` jumps to `next` and `break` to `end`
+    fn loop_body(&mut self, label: &Option<String>, next: &str, end: &str, body: &Node, typ: IntType, content: &mut String) {
+        self.loops.push((label.clone(), next.to_string(), end.to_string()));
+        self.block(body, typ, content);
+        self.loops.pop();
+    }
+
+    // leaves the result of `value` in al, ax or eax
+    fn ret(&mut self, value: Option<&Node>, typ: IntType, content: &mut String) {
+        let value = match value {
+            Some(value) => value,
+            None => return
+        };
+        if !is_constant(value) {
+            self.expression(value, content);
+            return;
+        }
+        if let Some(n) = immediate(value, typ, self.diagnostics) {
+            content.push_str(format!("\tmov {}, {}\n", register(typ.size()), n).as_str());
+        }
+    }
+
+    // names declared inside the block are dropped at its end, their slots stay reserved
+    fn block(&mut self, node: &Node, typ: IntType, content: &mut String) {
+        let outer = self.locals.len();
+        if let NodeKind::Scope(statements) = &node.kind {
+            for stat in statements {
+                self.statement(stat, typ, content);
+            }
+        }
+        self.locals.truncate(outer);
+    }
+
+    // labels are local to the function (`.else3`) and unique across the unit
+    fn label(&mut self, name: &str) -> String {
+        self.labels += 1;
+        format!(".{}{}", name, self.labels)
+    }
+
+    // jumps to `target` when `cond` is `when`, `&&` and `||` short circuit
+    fn branch(&mut self, cond: &Node, target: &str, when: bool, asm: &mut String) {
+        match &cond.kind {
+            NodeKind::Binary(op, lhs, rhs) if op.is_comparison() => {
+                if !self.operands(lhs, rhs, compared(lhs, rhs), asm) {
+                    return;
+                }
+                let op = if when { *op } else { op.negate() };
+                asm.push_str(format!("\tcmp eax, ecx\n\
+                                      \tj{} {}\n", condition_code(op, compare_signed(lhs, rhs)), target).as_str());
+            },
+            NodeKind::Binary(op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs) => {
+                // `a && b` is false as soon as `a` is, `a || b` true as soon as `a` is
+                let shortcut = *op == BinaryOp::Or;
+                if when == shortcut {
+                    self.branch(lhs, target, when, asm);
+                    self.branch(rhs, target, when, asm);
+                } else {
+                    let skip = self.label("skip");
+                    self.branch(lhs, &skip, shortcut, asm);
+                    self.branch(rhs, target, when, asm);
+                    asm.push_str(format!("{}:\n", skip).as_str());
+                }
+            },
+            NodeKind::Unary(UnaryOp::Not, operand) => self.branch(operand, target, !when, asm),
+            _ => {
+                if self.expression(cond, asm) {
+                    let jump = if when { "jnz" } else { "jz" };
+                    asm.push_str(format!("\ttest eax, eax\n\
+                                          \t{} {}\n", jump, target).as_str());
+                }
+            }
+        }
+    }
+
+    // cdecl call, arguments are pushed right to left as dwords and removed by
+    // the caller, the parser already checked them against the callee
+    fn call(&mut self, node: &Node, asm: &mut String) -> bool {
+        let (name, args) = match &node.kind {
+            NodeKind::Call(name, args) => (name, args),
+            _ => return false
+        };
+        let mut ok = true;
+        for arg in args.iter().rev() {
+            if is_constant(arg) {
+                match immediate(arg, int_type(&arg.typ), self.diagnostics) {
+                    Some(value) => asm.push_str(format!("\tpush dword {}\n", value).as_str()),
+                    None => ok = false
+                }
+                continue;
+            }
+            if self.expression(arg, asm) {
+                asm.push_str("\tpush eax\n");
+            } else {
+                ok = false;
+            }
+        }
+        if !ok {
+            return false;
+        }
+        asm.push_str(format!("\tcall {}\n", name).as_str());
+        if !args.is_empty() {
+            asm.push_str(format!("\tadd esp, {}\n", 4 * args.len()).as_str());
+        }
+        true
+    }
+
+    // left operand in eax and right operand in ecx, both converted to `typ`
+    fn operands(&mut self, lhs: &Node, rhs: &Node, typ: IntType, asm: &mut String) -> bool {
+        if !self.expression(lhs, asm) {
+            return false;
+        }
+        // the right operand goes through the stack unless it is a constant
+        if is_constant(rhs) {
+            match immediate(rhs, int_type(&rhs.typ), self.diagnostics) {
+                Some(value) => asm.push_str(format!("\tmov ecx, {}\n", value).as_str()),
+                None => return false
+            }
         } else {
-            panic!();
-        }
-    }
-    println!("{}", asm);
-    // let mut file = match File::create("./main.asm") {
-    //     Ok(file) => file,
-    //     Err(err) => {
-    //         eprintln!("Error creating file: {}", err);
-    //         return;
-    //     }
-    // };
-    // match file.write_all(asm.as_bytes()) {
-    //     Ok(_) => println!("Compiled to :: Path({}) as :: Output({})\n{} Byte => {}\n",
-    //                       file_name, output, file_name, buffer.len()),
-    //     Err(err) => eprintln!("Error writing to asm file: {}", err),
-    // }
+            asm.push_str("\tpush eax\n");
+            if !self.expression(rhs, asm) {
+                return false;
+            }
+            asm.push_str("\tmov ecx, eax\n\
+                          \tpop eax\n");
+        }
+        convert(int_type(&lhs.typ), typ, "eax", asm);
+        convert(int_type(&rhs.typ), typ, "ecx", asm);
+        true
+    }
+
+    // slot below ebp for a value of `size` bytes, aligned to `align`
+    fn allocate(&mut self, size: u32, align: u32) -> i32 {
+        self.stack_size = (self.stack_size + align - 1) & !(align - 1);
+        self.stack_size += size;
+        -(self.stack_size as i32)
+    }
+
+    // address of the place `node` into eax
+    fn address_of(&mut self, node: &Node, asm: &mut String) -> bool {
+        match &node.kind {
+            NodeKind::Identifier(name) => match self.lookup(name) {
+                Some(local) => asm.push_str(format!("\tlea eax, {}\n", address(local.offset)).as_str()),
+                None => {
+                    self.diagnostics.push(unsupported(node.span, "name"));
+                    return false;
+                }
+            },
+            NodeKind::Unary(UnaryOp::Deref, pointer) => return self.expression(pointer, asm),
+            NodeKind::Index(base, index) => {
+                // arrays are indexed where they are, pointers by their value
+                let based = match base.typ {
+                    Types::Array(..) => self.address_of(base, asm),
+                    _ => self.expression(base, asm)
+                };
+                if !based {
+                    return false;
+                }
+                let size = node.typ.detect(self.types);
+                if let NodeKind::NumberLit(n, _) = index.kind {
+                    if n != 0 {
+                        asm.push_str(format!("\tadd eax, {}\n", n * size as i64).as_str());
+                    }
+                    return true;
+                }
+                asm.push_str("\tpush eax\n");
+                if !self.expression(index, asm) {
+                    return false;
+                }
+                asm.push_str("\tmov ecx, eax\n\
+                              \tpop eax\n");
+                if matches!(size, 1 | 2 | 4 | 8) {
+                    asm.push_str(format!("\tlea eax, [eax+ecx*{}]\n", size).as_str());
+                } else {
+                    asm.push_str(format!("\timul ecx, ecx, {}\n\
+                                          \tadd eax, ecx\n", size).as_str());
+                }
+            },
+            NodeKind::Field(base, name) => {
+                if !self.address_of(base, asm) {
+                    return false;
+                }
+                let offset = match &base.typ {
+                    Types::Struct(s) => self.types.get(s).and_then(|l| l.field(name)).map_or(0, |f| f.offset),
+                    _ => 0
+                };
+                if offset != 0 {
+                    asm.push_str(format!("\tadd eax, {}\n", offset).as_str());
+                }
+            },
+            _ => {
+                self.diagnostics.push(unsupported(node.span, "place"));
+                return false;
+            }
+        }
+        true
+    }
+
+    // later declarations shadow earlier ones
+    fn lookup(&self, name: &str) -> Option<&Local> {
+        self.locals.iter().rev().find(|l| l.name == name)
+    }
+
+    // evaluates `node` into eax, values narrower than 32 bits are kept sign or
+    // zero extended so operands of different widths can be mixed freely
+    fn expression(&mut self, node: &Node, asm: &mut String) -> bool {
+        let typ = int_type(&node.typ);
+        if typ.size() == 8 {
+            self.diagnostics.push(unsupported(node.span, "64-bit value"));
+            return false;
+        }
+        // arrays and structs are only used through their members or their address
+        if node.typ.is_aggregate() {
+            let what = if let Types::Array(..) = node.typ { "array value" } else { "struct value" };
+            self.diagnostics.push(unsupported(node.span, what));
+            return false;
+        }
+        match &node.kind {
+            NodeKind::NumberLit(..) | NodeKind::String(_) => match immediate(node, typ, self.diagnostics) {
+                Some(value) => asm.push_str(format!("\tmov eax, {}\n", value).as_str()),
+                None => return false
+            },
+            NodeKind::Identifier(name) => match self.lookup(name) {
+                Some(local) => asm.push_str(load(local.typ, &address(local.offset)).as_str()),
+                None => {
+                    self.diagnostics.push(unsupported(node.span, "name"));
+                    return false;
+                }
+            },
+            // the result comes back in al, ax or eax depending on the return type
+            NodeKind::Call(..) => {
+                if !self.call(node, asm) {
+                    return false;
+                }
+                extend(int_type(&node.typ), asm);
+            },
+            NodeKind::Binary(BinaryOp::And | BinaryOp::Or, _, _) | NodeKind::Unary(UnaryOp::Not, _) => {
+                // logical values are materialized from the branches, 1 or 0
+                let (zero, end) = (self.label("false"), self.label("true"));
+                self.branch(node, &zero, false, asm);
+                asm.push_str(format!("\tmov eax, 1\n\
+                                      \tjmp {}\n\
+                                      {}:\n\
+                                      \txor eax, eax\n\
+                                      {}:\n", end, zero, end).as_str());
+            },
+            NodeKind::Unary(UnaryOp::AddrOf, place) => return self.address_of(place, asm),
+            NodeKind::Index(..) | NodeKind::Field(..) => {
+                if !self.address_of(node, asm) {
+                    return false;
+                }
+                asm.push_str(load(typ, "[eax]").as_str());
+            },
+            NodeKind::Unary(UnaryOp::Deref, pointer) => {
+                if !self.expression(pointer, asm) {
+                    return false;
+                }
+                asm.push_str(load(typ, "[eax]").as_str());
+            },
+            NodeKind::Unary(op, operand) => {
+                if !self.expression(operand, asm) {
+                    return false;
+                }
+                match op {
+                    UnaryOp::Neg => asm.push_str("\tneg eax\n"),
+                    UnaryOp::BitNot => asm.push_str("\tnot eax\n"),
+                    _ => unreachable!("handled above")
+                }
+                extend(typ, asm);
+            },
+            NodeKind::Binary(op, lhs, rhs) if op.is_comparison() => {
+                if !self.operands(lhs, rhs, compared(lhs, rhs), asm) {
+                    return false;
+                }
+                asm.push_str(format!("\tcmp eax, ecx\n\
+                                      \tset{} al\n\
+                                      \tmovzx eax, al\n", condition_code(*op, compare_signed(lhs, rhs))).as_str());
+            },
+            NodeKind::Binary(op, lhs, rhs) => {
+                if !self.operands(lhs, rhs, typ, asm) {
+                    return false;
+                }
+                // pointers move by whole elements, the integer side is scaled
+                let (left, right) = (element_size(&lhs.typ, self.types), element_size(&rhs.typ, self.types));
+                match (left, right) {
+                    (n, 0) if n > 1 => asm.push_str(format!("\timul ecx, ecx, {}\n", n).as_str()),
+                    (0, n) if n > 1 => asm.push_str(format!("\timul eax, eax, {}\n", n).as_str()),
+                    _ => {}
+                }
+                let signed = typ.is_signed();
+                match op {
+                    BinaryOp::Add => asm.push_str("\tadd eax, ecx\n"),
+                    // the distance between two pointers counts elements too
+                    BinaryOp::Sub if left > 1 && right > 1 => {
+                        asm.push_str("\tsub eax, ecx\n");
+                        if left.is_power_of_two() {
+                            asm.push_str(format!("\tsar eax, {}\n", left.trailing_zeros()).as_str());
+                        } else {
+                            asm.push_str(format!("\tmov ecx, {}\n\
+                                                  \tcdq\n\
+                                                  \tidiv ecx\n", left).as_str());
+                        }
+                    },
+                    BinaryOp::Sub => asm.push_str("\tsub eax, ecx\n"),
+                    BinaryOp::Mul if signed => asm.push_str("\timul eax, ecx\n"),
+                    BinaryOp::Mul => asm.push_str("\tmul ecx\n"),
+                    BinaryOp::Div | BinaryOp::Rem => {
+                        if signed {
+                            asm.push_str("\tcdq\n\
+                                          \tidiv ecx\n");
+                        } else {
+                            asm.push_str("\txor edx, edx\n\
+                                          \tdiv ecx\n");
+                        }
+                        if *op == BinaryOp::Rem {
+                            asm.push_str("\tmov eax, edx\n");
+                        }
+                    },
+                    BinaryOp::BitAnd => asm.push_str("\tand eax, ecx\n"),
+                    BinaryOp::BitOr => asm.push_str("\tor eax, ecx\n"),
+                    BinaryOp::BitXor => asm.push_str("\txor eax, ecx\n"),
+                    BinaryOp::Shl => asm.push_str("\tshl eax, cl\n"),
+                    // the left operand decides between arithmetic and logical shift
+                    BinaryOp::Shr if int_type(&lhs.typ).is_signed() => asm.push_str("\tsar eax, cl\n"),
+                    BinaryOp::Shr => asm.push_str("\tshr eax, cl\n"),
+                    _ => unreachable!("comparisons and logical operators are handled above")
+                }
+                extend(typ, asm);
+            },
+            _ => {
+                self.diagnostics.push(unsupported(node.span, "expression"));
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// string literals go to .rodata, each one followed by a 0 terminator, and
+// after them the switch jump tables
+fn rodata(strings: &[Vec<u8>], tables: &[String]) -> String {
+    if strings.is_empty() && tables.is_empty() {
+        return String::new();
+    }
+    let mut data = String::from("section .rodata\n");
+    for (index, bytes) in strings.iter().enumerate() {
+        let mut values: Vec<String> = bytes.iter().map(|b| b.to_string()).collect();
+        values.push("0".to_string());
+        data.push_str(format!("str.{}:\n\tdb {}\n", index, values.join(", ")).as_str());
+    }
+    if !tables.is_empty() {
+        data.push_str("align 4\n");
+    }
+    for table in tables {
+        data.push_str(table);
+    }
+    data
+}
+
+fn codegen(unit: &Unit, diagnostics: &mut Diagnostics) -> String {
+    // nasm assembles `-f bin` as 16-bit code unless told otherwise
+    let mut asm = String::from("bits 32\nsection .text\n");
+    let mut gen = Codegen::new(diagnostics, &unit.types);
+    // every function is exported so objects can be linked against each other
+    for nod in &unit.items {
+        if let NodeKind::Function(name, ..) = &nod.kind {
+            asm.push_str(format!("global {}\n", name).as_str());
+        }
+    }
+    for nod in &unit.items {
+        match &nod.kind {
+            NodeKind::Function(name, typ, param, body) => gen.function(name, int_type(typ), param, body, &mut asm),
+            _ => gen.diagnostics.push(unsupported(nod.span, "top level item"))
+        }
+    }
+    asm.push_str(rodata(&unit.strings, &gen.tables).as_str());
+    asm
 }
@@ -0,0 +1,285 @@
+use std::fmt::{Display, Formatter};
+use crate::source::{SourceMap, Span};
+
+// stable diagnostic codes, never renumber or reuse one, driver errors such as
+// an unreadable source file have no code and use NO_CODE
+pub const NO_CODE: &str = "";
+pub const E_UNEXPECTED_TOKEN: &str = "E0001";
+pub const E_EXPECTED_TOKEN: &str = "E0002";
+pub const E_UNKNOWN_TYPE: &str = "E0003";
+pub const E_UNCLOSED_DELIMITER: &str = "E0004";
+pub const E_LITERAL_OUT_OF_RANGE: &str = "E0005";
+pub const E_UNSUPPORTED_TYPE: &str = "E0006";
+pub const E_UNKNOWN_CHARACTER: &str = "E0007";
+pub const E_UNSUPPORTED: &str = "E0008";
+pub const E_ASSEMBLER: &str = "E0009";
+pub const E_UNTERMINATED_COMMENT: &str = "E0010";
+pub const E_INVALID_LITERAL: &str = "E0011";
+pub const E_MISMATCHED_TYPES: &str = "E0012";
+pub const E_UNTERMINATED_STRING: &str = "E0013";
+pub const E_INVALID_ESCAPE: &str = "E0014";
+pub const E_UNRESOLVED_NAME: &str = "E0015";
+pub const E_IMMUTABLE_ASSIGN: &str = "E0016";
+pub const E_OUTSIDE_LOOP: &str = "E0017";
+pub const E_DUPLICATE_CASE: &str = "E0018";
+pub const E_ARGUMENT_COUNT: &str = "E0019";
+pub const E_DUPLICATE_NAME: &str = "E0020";
+pub const E_NOT_LVALUE: &str = "E0021";
+pub const E_INDEX_OUT_OF_BOUNDS: &str = "E0022";
+pub const E_MISSING_FIELD: &str = "E0023";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool
+}
+
+// machine applicable fix, replacing `span` with `replacement`
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new()
+        }
+    }
+
+    pub fn warning(code: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message)
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label { span, message, primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label { span, message, primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_suggestion(mut self, span: Span, replacement: String, message: String) -> Self {
+        self.suggestions.push(Suggestion { span, replacement, message });
+        self
+    }
+
+    // span of the first primary label, used as the headline location
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|l| l.primary).or(self.labels.first()).map(|l| l.span)
+    }
+
+    // renders the diagnostic the way rustc does, with the source lines under the header
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = match self.code {
+            NO_CODE => format!("{}: {}\n", self.severity, self.message),
+            code => format!("{}[{}]: {}\n", self.severity, code, self.message)
+        };
+        // without a span or its source file there are no lines to show
+        let located = self.primary_span().and_then(|span| Some((span, sources.get(span.file)?, sources.describe(span)?)));
+        let (primary, file, at) = match located {
+            Some(located) => located,
+            None => {
+                for note in &self.notes {
+                    out.push_str(format!("  = note: {}\n", note).as_str());
+                }
+                for suggestion in &self.suggestions {
+                    out.push_str(format!("  = help: {}\n", suggestion.message).as_str());
+                }
+                return out;
+            }
+        };
+
+        let mut lines: Vec<usize> = self.labels.iter()
+            .filter(|l| l.span.file == primary.file)
+            .map(|l| file.line_index(l.span.start))
+            .collect();
+        lines.sort();
+        lines.dedup();
+        let gutter = (lines.last().copied().unwrap_or(0) + 1).to_string().len();
+        let pad = " ".repeat(gutter);
+
+        out.push_str(format!("{}--> {}\n", pad, at).as_str());
+        out.push_str(format!("{} |\n", pad).as_str());
+        for (n, &line) in lines.iter().enumerate() {
+            if n > 0 && line > lines[n - 1] + 1 {
+                out.push_str(format!("{}...\n", pad).as_str());
+            }
+            let text = file.line_text(line);
+            out.push_str(format!("{:>width$} | {}\n", line + 1, text, width = gutter).as_str());
+            for label in self.labels.iter().filter(|l| l.span.file == primary.file && file.line_index(l.span.start) == line) {
+                let start = file.location(label.span.start).column - 1;
+                // labels running past the end of the line are underlined up to the line end
+                let end = if file.line_index(label.span.end) == line {
+                    file.location(label.span.end).column - 1
+                } else {
+                    text.chars().count()
+                };
+                let marker = if label.primary { "^" } else { "-" };
+                let underline = marker.repeat(end.saturating_sub(start).max(1));
+                let row = format!("{} | {}{} {}", pad, " ".repeat(start), underline, label.message);
+                out.push_str(row.trim_end());
+                out.push('\n');
+            }
+        }
+        if !self.notes.is_empty() || !self.suggestions.is_empty() {
+            out.push_str(format!("{} |\n", pad).as_str());
+        }
+        for note in &self.notes {
+            out.push_str(format!("{} = note: {}\n", pad, note).as_str());
+        }
+        for suggestion in &self.suggestions {
+            out.push_str(format!("{} = help: {}\n", pad, suggestion.message).as_str());
+        }
+        out
+    }
+
+    // one line JSON object, the format read by editors and CI bots
+    pub fn to_json(&self, sources: &SourceMap) -> String {
+        let labels: Vec<String> = self.labels.iter()
+            .map(|l| format!("{{\"span\":{},\"message\":{},\"primary\":{}}}",
+                             span_json(l.span, sources), json_string(&l.message), l.primary))
+            .collect();
+        let notes: Vec<String> = self.notes.iter().map(|n| json_string(n)).collect();
+        let suggestions: Vec<String> = self.suggestions.iter()
+            .map(|s| format!("{{\"span\":{},\"replacement\":{},\"message\":{}}}",
+                             span_json(s.span, sources), json_string(&s.replacement), json_string(&s.message)))
+            .collect();
+        let (file, span) = match self.primary_span().and_then(|span| Some((sources.get(span.file)?, span))) {
+            Some((file, span)) => (json_string(&file.name), span_json(span, sources)),
+            None => ("null".to_string(), "null".to_string())
+        };
+        let code = match self.code {
+            NO_CODE => "null".to_string(),
+            code => json_string(code)
+        };
+        format!("{{\"severity\":\"{}\",\"code\":{},\"message\":{},\"file\":{},\"span\":{},\"labels\":[{}],\"notes\":[{}],\"suggestions\":[{}],\"rendered\":{}}}",
+                self.severity, code, json_string(&self.message), file, span,
+                labels.join(","), notes.join(","), suggestions.join(","), json_string(&self.render(sources)))
+    }
+}
+
+// `null` when the file of `span` is not in `sources`
+fn span_json(span: Span, sources: &SourceMap) -> String {
+    let file = match sources.get(span.file) {
+        Some(file) => file,
+        None => return "null".to_string()
+    };
+    let start = file.location(span.start);
+    let end = file.location(span.end);
+    format!("{{\"start\":{},\"end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{}}}",
+            span.start, span.end, start.line, start.column, end.line, end.column)
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.items.push(diagnostic);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.items.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        for diagnostic in &self.items {
+            out.push_str(diagnostic.render(sources).as_str());
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_json(&self, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        for diagnostic in &self.items {
+            out.push_str(diagnostic.to_json(sources).as_str());
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -1,14 +1,15 @@
-use std::fmt::{Display, Formatter, write};
-use std::fs::File;
-use std::io::{Read, Write};
-use std::process::exit;
+use std::fmt::{Display, Formatter};
+use crate::diagnostics::{Diagnostic, Diagnostics, E_INVALID_ESCAPE, E_INVALID_LITERAL, E_UNKNOWN_CHARACTER, E_UNTERMINATED_COMMENT, E_UNTERMINATED_STRING};
+use crate::source::{FileId, Span};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
    None,
 
    Int,
    Float,
+   Str,
+   Char,
    Uint0,
    Int8,
    Uint8,
@@ -22,26 +23,77 @@ pub enum TokenKind {
    // ketwords
    Defun,
    Let,
+   Mut,
    Return,
+   If,
+   Else,
+   While,
+   Loop,
+   For,
+   In,
+   Break,
+   Continue,
+   Switch,
+   Case,
+   Default,
+   Null,
+   Struct,
 
    // separators
    Lbrace,
    Rbrace,
    Lparen,
    Rparen,
+   Lbracket,
+   Rbracket,
    Arrow,
+   FatArrow,
    Equal,
    Same,
    Colon,
    Comma,
+   DotDot,
+   Dot,
    Semi,
    
    // operators
    Plus,
    Minus,
+   Star,
+   Slash,
+   Percent,
+   Amp,
+   Pipe,
+   Caret,
+   Tilde,
+   Bang,
+   Shl,
+   Shr,
+   AndAnd,
+   OrOr,
+   BangEqual,
+   Less,
+   LessEqual,
+   Greater,
+   GreaterEqual,
+   PlusEqual,
+   MinusEqual,
+   StarEqual,
+   SlashEqual,
+   PercentEqual,
+   AmpEqual,
+   PipeEqual,
+   CaretEqual,
+   ShlEqual,
+   ShrEqual,
+   PlusPlus,
+   MinusMinus,
 
    // etc
+   Error,
    WhiteSpace,
+   Comment,
+   DocComment,
    Eof,
    Identifier
 }
@@ -52,6 +104,8 @@ impl Display for TokenKind {
          TokenKind::None => write!(f, "None"),
          TokenKind::Int => write!(f, "Int"),
          TokenKind::Float => write!(f, "Float"),
+         TokenKind::Str => write!(f, "Str"),
+         TokenKind::Char => write!(f, "Char"),
          TokenKind::Uint0 => write!(f, "Uint0"),
          TokenKind::Int8 => write!(f, "Int8"),
          TokenKind::Uint8 => write!(f, "Uint8"),
@@ -63,201 +117,567 @@ impl Display for TokenKind {
          TokenKind::Uint64 => write!(f, "Uint64"),
          TokenKind::Defun => write!(f, "Defun"),
          TokenKind::Let => write!(f, "Let"),
+         TokenKind::Mut => write!(f, "Mut"),
          TokenKind::Return => write!(f, "Return"),
+         TokenKind::If => write!(f, "If"),
+         TokenKind::Else => write!(f, "Else"),
+         TokenKind::While => write!(f, "While"),
+         TokenKind::Loop => write!(f, "Loop"),
+         TokenKind::For => write!(f, "For"),
+         TokenKind::In => write!(f, "In"),
+         TokenKind::Break => write!(f, "Break"),
+         TokenKind::Continue => write!(f, "Continue"),
+         TokenKind::Switch => write!(f, "Switch"),
+         TokenKind::Case => write!(f, "Case"),
+         TokenKind::Default => write!(f, "Default"),
+         TokenKind::Null => write!(f, "Null"),
+         TokenKind::Struct => write!(f, "Struct"),
          TokenKind::Lbrace => write!(f, "Lbrace"),
          TokenKind::Rbrace => write!(f, "Rbrace"),
          TokenKind::Lparen => write!(f, "Lparen"),
          TokenKind::Rparen => write!(f, "Rparen"),
+         TokenKind::Lbracket => write!(f, "Lbracket"),
+         TokenKind::Rbracket => write!(f, "Rbracket"),
          TokenKind::Arrow => write!(f, "Arrow"),
+         TokenKind::FatArrow => write!(f, "FatArrow"),
          TokenKind::Equal => write!(f, "Equal"),
          TokenKind::Same => write!(f, "Same"),
          TokenKind::Colon => write!(f, "Colon"),
          TokenKind::Comma => write!(f, "Comma"),
+         TokenKind::DotDot => write!(f, "DotDot"),
+         TokenKind::Dot => write!(f, "Dot"),
          TokenKind::Semi => write!(f, "Semi"),
          TokenKind::Plus => write!(f, "Plus"), 
          TokenKind::Minus => write!(f, "Minus"),
+         TokenKind::Star => write!(f, "Star"),
+         TokenKind::Slash => write!(f, "Slash"),
+         TokenKind::Percent => write!(f, "Percent"),
+         TokenKind::Amp => write!(f, "Amp"),
+         TokenKind::Pipe => write!(f, "Pipe"),
+         TokenKind::Caret => write!(f, "Caret"),
+         TokenKind::Tilde => write!(f, "Tilde"),
+         TokenKind::Bang => write!(f, "Bang"),
+         TokenKind::Shl => write!(f, "Shl"),
+         TokenKind::Shr => write!(f, "Shr"),
+         TokenKind::AndAnd => write!(f, "AndAnd"),
+         TokenKind::OrOr => write!(f, "OrOr"),
+         TokenKind::BangEqual => write!(f, "BangEqual"),
+         TokenKind::Less => write!(f, "Less"),
+         TokenKind::LessEqual => write!(f, "LessEqual"),
+         TokenKind::Greater => write!(f, "Greater"),
+         TokenKind::GreaterEqual => write!(f, "GreaterEqual"),
+         TokenKind::PlusEqual => write!(f, "PlusEqual"),
+         TokenKind::MinusEqual => write!(f, "MinusEqual"),
+         TokenKind::StarEqual => write!(f, "StarEqual"),
+         TokenKind::SlashEqual => write!(f, "SlashEqual"),
+         TokenKind::PercentEqual => write!(f, "PercentEqual"),
+         TokenKind::AmpEqual => write!(f, "AmpEqual"),
+         TokenKind::PipeEqual => write!(f, "PipeEqual"),
+         TokenKind::CaretEqual => write!(f, "CaretEqual"),
+         TokenKind::ShlEqual => write!(f, "ShlEqual"),
+         TokenKind::ShrEqual => write!(f, "ShrEqual"),
+         TokenKind::PlusPlus => write!(f, "PlusPlus"),
+         TokenKind::MinusMinus => write!(f, "MinusMinus"),
+         TokenKind::Error => write!(f, "Error"),
          TokenKind::WhiteSpace => write!(f, "WhiteSpace"),
+         TokenKind::Comment => write!(f, "Comment"),
+         TokenKind::DocComment => write!(f, "DocComment"),
          TokenKind::Eof => write!(f, "Eof"),
          TokenKind::Identifier => write!(f, "Identifier")
       }
    } 
 }
 
+// bytes of a string or char literal body (quotes stripped), the lexer has
+// already checked every escape
+pub fn unescape(body: &str) -> Vec<u8> {
+   let mut bytes = Vec::new();
+   let mut chars = body.chars();
+   while let Some(c) = chars.next() {
+      if c != '\\' {
+         let mut buffer = [0; 4];
+         bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+         continue;
+      }
+      match chars.next() {
+         Some('n') => bytes.push(b'\n'),
+         Some('t') => bytes.push(b'\t'),
+         Some('r') => bytes.push(b'\r'),
+         Some('0') => bytes.push(0),
+         Some('x') => {
+            let hex: String = chars.by_ref().take(2).collect();
+            bytes.push(u8::from_str_radix(&hex, 16).unwrap_or(0));
+         }
+         Some(c) => bytes.push(c as u8),
+         None => {}
+      }
+   }
+   bytes
+}
+
+// identifiers follow Unicode XID, plus a leading `_`
+fn is_ident_start(c: char) -> bool {
+   c == '_' || unicode_ident::is_xid_start(c)
+}
+
+fn is_ident_continue(c: char) -> bool {
+   unicode_ident::is_xid_continue(c)
+}
+
+// punctuation and operator tokens by their exact text
+fn punctuation(text: &str) -> Option<TokenKind> {
+   let kind = match text {
+      "<<=" => TokenKind::ShlEqual,
+      ">>=" => TokenKind::ShrEqual,
+      "->" => TokenKind::Arrow,
+      "=>" => TokenKind::FatArrow,
+      "==" => TokenKind::Same,
+      "<<" => TokenKind::Shl,
+      ">>" => TokenKind::Shr,
+      "&&" => TokenKind::AndAnd,
+      "||" => TokenKind::OrOr,
+      "!=" => TokenKind::BangEqual,
+      "<=" => TokenKind::LessEqual,
+      ">=" => TokenKind::GreaterEqual,
+      "+=" => TokenKind::PlusEqual,
+      "-=" => TokenKind::MinusEqual,
+      "*=" => TokenKind::StarEqual,
+      "/=" => TokenKind::SlashEqual,
+      "%=" => TokenKind::PercentEqual,
+      "&=" => TokenKind::AmpEqual,
+      "|=" => TokenKind::PipeEqual,
+      "^=" => TokenKind::CaretEqual,
+      "++" => TokenKind::PlusPlus,
+      "--" => TokenKind::MinusMinus,
+      ".." => TokenKind::DotDot,
+      "." => TokenKind::Dot,
+      "{" => TokenKind::Lbrace,
+      "}" => TokenKind::Rbrace,
+      "(" => TokenKind::Lparen,
+      ")" => TokenKind::Rparen,
+      "[" => TokenKind::Lbracket,
+      "]" => TokenKind::Rbracket,
+      "=" => TokenKind::Equal,
+      ":" => TokenKind::Colon,
+      "," => TokenKind::Comma,
+      ";" => TokenKind::Semi,
+      "+" => TokenKind::Plus,
+      "-" => TokenKind::Minus,
+      "*" => TokenKind::Star,
+      "/" => TokenKind::Slash,
+      "%" => TokenKind::Percent,
+      "&" => TokenKind::Amp,
+      "|" => TokenKind::Pipe,
+      "^" => TokenKind::Caret,
+      "~" => TokenKind::Tilde,
+      "!" => TokenKind::Bang,
+      "<" => TokenKind::Less,
+      ">" => TokenKind::Greater,
+      _ => return None
+   };
+   Some(kind)
+}
+
+pub const INT_SUFFIXES: [&str; 8] = ["i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64"];
+
+// whitespace and comments ride along on the next real token as `leading`
+// trivia, the rest of the line after a token is its `trailing` trivia,
+// only filled in by `Lexer::lossless`
 #[derive(Debug)]
 pub struct Token {
    pub kind: TokenKind,
-   pub span: String
+   pub text: String,
+   pub span: Span,
+   pub leading: Vec<Token>,
+   pub trailing: Vec<Token>
 }
 
 impl Token {
-   fn new(kind: TokenKind, span: String) -> Self {
-      Self { kind, span }
+   fn new(kind: TokenKind, text: String, span: Span) -> Self {
+      Self { kind, text, span, leading: Vec::new(), trailing: Vec::new() }
+   }
+
+   pub fn is_trivia(&self) -> bool {
+      matches!(self.kind, TokenKind::WhiteSpace | TokenKind::Comment | TokenKind::DocComment)
+   }
+
+   // exact source text of the token with its trivia
+   pub fn full_text(&self) -> String {
+      let mut text = String::new();
+      for trivia in &self.leading {
+         text.push_str(&trivia.text);
+      }
+      if self.kind != TokenKind::Eof {
+         text.push_str(&self.text);
+      }
+      for trivia in &self.trailing {
+         text.push_str(&trivia.text);
+      }
+      text
    }
 }
 
+// glues a lossless token stream back into the original source
+pub fn untokenize(tokens: &[Token]) -> String {
+   tokens.iter().map(|t| t.full_text()).collect()
+}
+
 pub struct Lexer<'a> {
    code: &'a str,
+   file: FileId,
+   diagnostics: &'a mut Diagnostics,
    pos: usize
 }
 
 impl<'a> Lexer<'a> {
-   pub fn new(code: &'a str) -> Self {
-      Self { code, pos: 0 }
+   pub fn new(code: &'a str, file: FileId, diagnostics: &'a mut Diagnostics) -> Self {
+      Self { code, file, diagnostics, pos: 0 }
    }
 
+   // None once the Eof token has been handed out, malformed input becomes an
+   // Error token covering the bad text and a diagnostic, then lexing goes on
    pub fn tokenize(&mut self) -> Option<Token> {
+      let start = self.pos;
       if self.pos == self.code.len() {
          self.pos += 1;
-         return Some(Token::new(TokenKind::Eof, "\0".to_string()));
+         let end = self.code.len();
+         return Some(Token::new(TokenKind::Eof, "\0".to_string(), Span::new(self.file, end, end)));
       }
-      if let Some(c) = self.lexer_peek_code() {
-         let mut kind = TokenKind::None;
-         let mut span = String::new();
-         if c.is_whitespace() {
-            kind = TokenKind::WhiteSpace;
-            self.pos += 1;
-         } else if c.is_digit(10) {
-            while let Some(c) = self.lexer_peek_code() {
-               if c.is_digit(10) {
-                  kind = TokenKind::Int;
-                  self.pos += 1;
-                  span.push(c);
-               } else {
-                  let valid = match self.lexer_peek_advance() {
-                     Some(c) if c.is_digit(10) => true,
-                     _ => false
-                  };
-                  if c == '.' && valid {
-                     span.push('.');
-                     while let Some(c) = self.lexer_peek_code() {
-                        if !c.is_digit(10) {break}
-                        self.pos += 1;
-                        span.push(c);
-                     }
-                  } else {
-                     self.pos -= 1;
-                     break;
-                  }
-                  kind = TokenKind::Float;
-                  break;
+      let c = self.lexer_peek_code()?;
+      let mut text = String::new();
+      let kind = match self.lexer_token(c, start, &mut text) {
+         Ok(kind) => kind,
+         Err(e) => {
+            self.diagnostics.push(e);
+            text = self.code[start..self.pos].to_string();
+            TokenKind::Error
+         }
+      };
+      Some(Token::new(kind, text, self.lexer_span(start)))
+   }
+
+   // every token up to and including Eof with whitespace and comments kept
+   // as trivia, `untokenize` on the result gives back the source unchanged
+   pub fn lossless(&mut self) -> Vec<Token> {
+      let mut tokens: Vec<Token> = Vec::new();
+      let mut leading: Vec<Token> = Vec::new();
+      // trailing trivia stops at the first line break after a token
+      let mut same_line = false;
+      while let Some(token) = self.tokenize() {
+         if token.is_trivia() {
+            let pending = match tokens.last_mut() {
+               Some(last) if same_line && !token.text.contains('\n') => &mut last.trailing,
+               _ => {
+                  same_line = false;
+                  &mut leading
+               }
+            };
+            // runs of whitespace come out of `tokenize` one character at a time
+            match pending.last_mut() {
+               Some(prev) if prev.kind == TokenKind::WhiteSpace && token.kind == TokenKind::WhiteSpace => {
+                  prev.text.push_str(&token.text);
+                  prev.span = prev.span.to(token.span);
                }
+               _ => pending.push(token)
             }
-         } else if c.is_alphanumeric() {
-            let mut buffer = String::new();
-            while let Some(x) = self.lexer_peek_code() {
-               if !x.is_alphanumeric() {break}
-               buffer.push(x);
-               self.pos += 1;
+            continue;
+         }
+         let eof = token.kind == TokenKind::Eof;
+         tokens.push(Token { leading: std::mem::take(&mut leading), ..token });
+         same_line = true;
+         if eof {break}
+      }
+      tokens
+   }
+
+   fn lexer_token(&mut self, c: char, start: usize, text: &mut String) -> Result<TokenKind, Diagnostic> {
+      let kind;
+      if c.is_whitespace() {
+         kind = TokenKind::WhiteSpace;
+         text.push(c);
+         self.lexer_bump();
+      } else if c == '/' && self.lexer_peek_code_more() == Some('/') {
+         kind = self.lexer_line_comment(text);
+      } else if c == '/' && self.lexer_peek_code_more() == Some('*') {
+         kind = self.lexer_block_comment(text, start)?;
+      } else if c.is_ascii_digit() {
+         kind = self.lexer_number(text, start)?;
+      } else if is_ident_start(c) {
+         while let Some(x) = self.lexer_peek_code() {
+            if !is_ident_continue(x) {break}
+            text.push(x);
+            self.lexer_bump();
+         }
+         kind = match text.as_str() {
+            "defun" => TokenKind::Defun,
+            "let" => TokenKind::Let,
+            "mut" => TokenKind::Mut,
+            "return" => TokenKind::Return,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "while" => TokenKind::While,
+            "loop" => TokenKind::Loop,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "switch" => TokenKind::Switch,
+            "case" => TokenKind::Case,
+            "default" => TokenKind::Default,
+            "null" => TokenKind::Null,
+            "struct" => TokenKind::Struct,
+            "u0" => TokenKind::Uint0,
+            "i8" => TokenKind::Int8,
+            "u8" => TokenKind::Uint8,
+            "i16" => TokenKind::Int16,
+            "u16" => TokenKind::Uint16,
+            "i32" => TokenKind::Int32,
+            "u32" => TokenKind::Uint32,
+            "i64" => TokenKind::Int64,
+            "u64" => TokenKind::Uint64,
+            _ => TokenKind::Identifier
+         };
+      } else if c == '"' {
+         kind = self.lexer_string(text, start)?;
+      } else if c == '\'' {
+         kind = self.lexer_char(text, start)?;
+      } else if c.is_ascii_punctuation() {
+         // maximal munch, `<<=` wins over `<<` which wins over `<`
+         let ahead: String = self.code[self.pos..].chars().take(3).collect();
+         let longest = (1..=ahead.chars().count()).rev()
+            .map(|n| ahead.chars().take(n).collect::<String>())
+            .find_map(|op| punctuation(&op).map(|kind| (op, kind)));
+         match longest {
+            Some((op, k)) => {
+               self.pos += op.len();
+               text.push_str(&op);
+               kind = k;
             }
-            let input = buffer.as_str();
-            kind = match input {
-               "defun" => TokenKind::Defun,
-               "let" => TokenKind::Let,
-               "return" => TokenKind::Return,
-               "u0" => TokenKind::Uint0,
-               "i8" => TokenKind::Int8,
-               "u8" => TokenKind::Uint8,
-               "i16" => TokenKind::Int16,
-               "u16" => TokenKind::Uint16,
-               "i32" => TokenKind::Int32,
-               "u32" => TokenKind::Uint32,
-               "i64" => TokenKind::Int64,
-               "u64" => TokenKind::Uint64,
-               _ => TokenKind::Identifier
-            };
-            span.push_str(buffer.as_str());
-         } else if c.is_ascii_punctuation() {
-            if let Some(p) = self.lexer_peek_code() {
-               if !p.is_ascii_punctuation() { panic!("Unexpected Token") }
-               kind = match p {
-                  '{' => {
-                     span.push(p);
-                     TokenKind::Lbrace
-                  },
-                  '}' => {
-                     span.push(p);
-                     TokenKind::Rbrace
-                  }
-                  '(' => {
-                     span.push(p);
-                     TokenKind::Lparen
-                  }
-                  ')' => {
-                     span.push(p);
-                     TokenKind::Rparen
-                  }
-                  '+' => {
-                     span.push(p);
-                     TokenKind::Plus
-                  }
-                  '-' => {
-                     if let Some(x) = self.lexer_peek_code_more() {
-                        self.pos += 1;
-                        let mut kind = TokenKind::None;
-                        if x == '>' {
-                           kind = TokenKind::Arrow;
-                           span.push_str("->");
-                        }
-                        kind
-                     } else {
-                        span.push('-');
-                        TokenKind::Minus
-                     }
-                  },
-                  '=' => {
-                     let mut kind = TokenKind::None;
-                     match self.lexer_peek_code_more() {
-                        Some(w) if !w.is_whitespace() => {
-                           if w == '=' {
-                              kind = TokenKind::Same;
-                              span.push_str("==");
-                           }
-                           kind
-                        }
-                        _ => {
-                           kind = TokenKind::Equal;
-                           span.push('=');
-                           kind
-                        }
-                     }
-                  }
-                  ':' => {
-                     span.push(p);
-                     TokenKind::Colon
-                  }
-                  ',' => {
-                     span.push(p);
-                     TokenKind::Comma
+            None => {
+               self.lexer_bump();
+               return Err(Diagnostic::error(E_UNKNOWN_CHARACTER, format!("unknown start of token: {}", c))
+                  .with_label(self.lexer_span(start), "character not allowed here".to_string()));
+            }
+         }
+      } else {
+         self.lexer_bump();
+         return Err(Diagnostic::error(E_UNKNOWN_CHARACTER, format!("unknown start of token: {}", c.escape_debug()))
+            .with_label(self.lexer_span(start), "character not allowed here".to_string()));
+      }
+      Ok(kind)
+   }
+
+   // `//` runs to the end of the line, `///` (but not `////`) is a doc comment
+   fn lexer_line_comment(&mut self, text: &mut String) -> TokenKind {
+      while let Some(c) = self.lexer_peek_code() {
+         if c == '\n' {break}
+         text.push(c);
+         self.lexer_bump();
+      }
+      if text.starts_with("///") && !text.starts_with("////") {
+         TokenKind::DocComment
+      } else {
+         TokenKind::Comment
+      }
+   }
+
+   // block comments nest, `/* a /* b */ c */` is a single comment
+   fn lexer_block_comment(&mut self, text: &mut String, start: usize) -> Result<TokenKind, Diagnostic> {
+      let mut depth = 0;
+      while let Some(c) = self.lexer_peek_code() {
+         let next = self.lexer_peek_code_more();
+         if c == '/' && next == Some('*') {
+            depth += 1;
+         } else if c == '*' && next == Some('/') {
+            depth -= 1;
+         } else {
+            text.push(c);
+            self.lexer_bump();
+            continue;
+         }
+         text.push(c);
+         text.extend(next);
+         self.pos += 2;
+         if depth == 0 {
+            return Ok(TokenKind::Comment);
+         }
+      }
+      Err(Diagnostic::error(E_UNTERMINATED_COMMENT, "unterminated block comment".to_string())
+         .with_label(self.lexer_span(start), "comment starts here and never ends".to_string()))
+   }
+
+   // `0x`/`0b`/`0o` prefixes, `_` separators and an `i8`..`u64` suffix, the
+   // whole literal stays in `text` and the parser works out its value
+   fn lexer_number(&mut self, text: &mut String, start: usize) -> Result<TokenKind, Diagnostic> {
+      let radix = match (self.lexer_peek_code(), self.lexer_peek_code_more()) {
+         (Some('0'), Some('x')) => 16,
+         (Some('0'), Some('b')) => 2,
+         (Some('0'), Some('o')) => 8,
+         _ => 10
+      };
+      if radix != 10 {
+         text.extend(self.lexer_peek_code());
+         text.extend(self.lexer_peek_code_more());
+         self.pos += 2;
+      }
+      let digits_start = text.len();
+      while let Some(c) = self.lexer_peek_code() {
+         if !c.is_ascii_alphanumeric() && c != '_' {break}
+         text.push(c);
+         self.lexer_bump();
+      }
+      let is_decimal = radix == 10 && text.chars().all(|c| c.is_ascii_digit() || c == '_');
+      if is_decimal && self.lexer_peek_code() == Some('.') && matches!(self.lexer_peek_code_more(), Some(c) if c.is_ascii_digit()) {
+         text.push('.');
+         self.lexer_bump();
+         while let Some(c) = self.lexer_peek_code() {
+            if !c.is_ascii_digit() && c != '_' {break}
+            text.push(c);
+            self.lexer_bump();
+         }
+         return Ok(TokenKind::Float);
+      }
+
+      let invalid = |lexer: &Self, message: String, label: &str| {
+         Diagnostic::error(E_INVALID_LITERAL, message).with_label(lexer.lexer_span(start), label.to_string())
+      };
+      let body = &text[digits_start..];
+      let (digits, suffix) = match body.find(['i', 'u']) {
+         Some(at) => body.split_at(at),
+         None => (body, "")
+      };
+      if !suffix.is_empty() && !INT_SUFFIXES.contains(&suffix) {
+         return Err(invalid(self, format!("invalid suffix `{}` for number literal", suffix), "invalid suffix")
+            .with_note("the suffix must be one of the integer types: i8, u8, i16, u16, i32, u32, i64, u64".to_string()));
+      }
+      if let Some(c) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+         return Err(invalid(self, format!("invalid digit `{}` in a base {} literal", c, radix), "invalid digit"));
+      }
+      if !digits.chars().any(|c| c != '_') {
+         return Err(invalid(self, "no valid digits found for number".to_string(), "expected digits"));
+      }
+      Ok(TokenKind::Int)
+   }
+
+   // `"..."`, the raw text including quotes and escapes ends up in `text`
+   fn lexer_string(&mut self, text: &mut String, start: usize) -> Result<TokenKind, Diagnostic> {
+      text.push('"');
+      self.lexer_bump();
+      while let Some(c) = self.lexer_peek_code() {
+         match c {
+            '"' => {
+               text.push(c);
+               self.lexer_bump();
+               return Ok(TokenKind::Str);
+            }
+            '\\' => self.lexer_escape(text),
+            _ => {
+               text.push(c);
+               self.lexer_bump();
+            }
+         }
+      }
+      Err(Diagnostic::error(E_UNTERMINATED_STRING, "unterminated double quote string".to_string())
+         .with_label(self.lexer_span(start), "string starts here and never ends".to_string()))
+   }
+
+   // `'c'` holds exactly one byte, either an ASCII character or an escape
+   fn lexer_char(&mut self, text: &mut String, start: usize) -> Result<TokenKind, Diagnostic> {
+      text.push('\'');
+      self.lexer_bump();
+      match self.lexer_peek_code() {
+         Some('\\') => self.lexer_escape(text),
+         Some(c) if c != '\'' && c != '\n' => {
+            if !c.is_ascii() {
+               self.lexer_bump();
+               return Err(Diagnostic::error(E_INVALID_LITERAL, "non-ASCII character in char literal".to_string())
+                  .with_label(self.lexer_span(start), "char literals hold a single byte".to_string())
+                  .with_note("use a string literal or a `\\xNN` escape".to_string()));
+            }
+            text.push(c);
+            self.lexer_bump();
+         }
+         _ => {
+            if self.lexer_peek_code() == Some('\'') {
+               self.lexer_bump();
+            }
+            return Err(Diagnostic::error(E_INVALID_LITERAL, "empty character literal".to_string())
+               .with_label(self.lexer_span(start), "expected one character".to_string()))
+         }
+      }
+      if self.lexer_peek_code() != Some('\'') {
+         // swallow the rest of `'abc'` so the closing quote does not open another literal
+         let rest = &self.code[self.pos..];
+         let line = rest.find('\n').unwrap_or(rest.len());
+         if let Some(close) = rest[..line].find('\'') {
+            self.pos += close + 1;
+         }
+         return Err(Diagnostic::error(E_INVALID_LITERAL, "character literal may only contain one character".to_string())
+            .with_label(self.lexer_span(start), "expected `'`".to_string()));
+      }
+      text.push('\'');
+      self.lexer_bump();
+      Ok(TokenKind::Char)
+   }
+
+   // one escape sequence starting at the current `\\`, a bad escape is reported
+   // and skipped so the rest of the literal still lexes
+   fn lexer_escape(&mut self, text: &mut String) {
+      let start = self.pos;
+      text.push('\\');
+      self.lexer_bump();
+      match self.lexer_peek_code() {
+         Some(c @ ('n' | 't' | 'r' | '0' | '\\' | '\'' | '"')) => {
+            text.push(c);
+            self.lexer_bump();
+         }
+         Some('x') => {
+            text.push('x');
+            self.lexer_bump();
+            for _ in 0..2 {
+               match self.lexer_peek_code() {
+                  Some(h) if h.is_ascii_hexdigit() => {
+                     text.push(h);
+                     self.lexer_bump();
                   }
-                  ';' => {
-                     span.push(p);
-                     TokenKind::Semi
+                  _ => {
+                     self.diagnostics.push(Diagnostic::error(E_INVALID_ESCAPE, "invalid `\\x` escape".to_string())
+                        .with_label(self.lexer_span(start), "expected two hex digits like `\\x0A`".to_string()));
+                     return;
                   }
-                  _ => TokenKind::None,
-               };
-               self.pos += 1;
+               }
             }
-         } else{
-            eprintln!("Token Not Exist");
-            exit(-1);
          }
-         Some(Token::new(kind, span))
-      } else {
-         None
+         Some(c) => {
+            text.push(c);
+            self.lexer_bump();
+            self.diagnostics.push(Diagnostic::error(E_INVALID_ESCAPE, format!("unknown character escape: `{}`", c.escape_debug()))
+               .with_label(self.lexer_span(start), "unknown escape".to_string())
+               .with_note("supported escapes are \\n \\t \\r \\0 \\\\ \\' \\\" and \\xNN".to_string()));
+         }
+         // the enclosing literal reports the missing terminator
+         None => {}
       }
    }
 
+   // `pos` is a byte offset into `code` and always sits on a char boundary
+   fn lexer_span(&self, start: usize) -> Span {
+      Span::new(self.file, start, self.pos.min(self.code.len()))
+   }
+
    fn lexer_peek_code(&self) -> Option<char> {
-      self.code.chars().nth(self.pos)
+      self.code.get(self.pos..)?.chars().next()
    }
 
    fn lexer_peek_code_more(&self) -> Option<char> {
-      self.code.chars().nth(self.pos+1)
+      let mut chars = self.code.get(self.pos..)?.chars();
+      chars.next();
+      chars.next()
    }
 
-   fn lexer_peek_advance(&mut self) -> Option<char> {
-      self.pos += 1;
-      let c = self.lexer_peek_code();
-      c
+   fn lexer_bump(&mut self) {
+      if let Some(c) = self.lexer_peek_code() {
+         self.pos += c.len_utf8();
+      }
    }
-}
\ No newline at end of file
+}
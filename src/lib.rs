@@ -0,0 +1,69 @@
+//! Suco compiler as a library, the `suco` binary is a thin wrapper around it.
+//!
+//! `compile_str` runs the whole pipeline, `tokenize` and `parse` stop after the
+//! lexer and parser. `tokenize_lossless` keeps whitespace and comments as token
+//! trivia for formatters, `untokenize` turns such a stream back into the source.
+//!
+//! Spans in tokens, nodes and diagnostics refer to file id 0 of the source that
+//! was passed in, `render` formats diagnostics against that same source.
+
+pub mod compilation_unit;
+pub mod diagnostics;
+pub mod lexer;
+pub mod parser;
+pub mod source;
+
+pub use compilation_unit::{Artifact, Emit, ErrorFormat, Options};
+pub use diagnostics::{Diagnostic, Diagnostics, Severity};
+pub use lexer::{untokenize, Token, TokenKind};
+pub use parser::{Node, Unit};
+pub use source::{FileId, SourceMap, Span};
+
+// `options.input` is only used as the file name in diagnostics
+pub fn compile_str(source: &str, options: &Options) -> Result<Artifact, Diagnostics> {
+    let sources = source_map(source, options);
+    compilation_unit::compile(&sources, 0, options.emit)
+}
+
+// diagnostics of `compile_str`, `tokenize` or `parse` on `source`, formatted
+// the way `options.error_format` asks for
+pub fn render(source: &str, options: &Options, diagnostics: &Diagnostics) -> String {
+    let sources = source_map(source, options);
+    match options.error_format {
+        ErrorFormat::Human => diagnostics.render(&sources),
+        ErrorFormat::Json => diagnostics.to_json(&sources)
+    }
+}
+
+fn source_map(source: &str, options: &Options) -> SourceMap {
+    let mut sources = SourceMap::new();
+    sources.add(options.input.clone(), source.to_string());
+    sources
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Diagnostics> {
+    let mut diagnostics = Diagnostics::new();
+    let tokens = compilation_unit::lex(source, 0, &mut diagnostics);
+    if diagnostics.has_errors() {
+        return Err(diagnostics);
+    }
+    Ok(tokens)
+}
+
+pub fn tokenize_lossless(source: &str) -> Result<Vec<Token>, Diagnostics> {
+    let mut diagnostics = Diagnostics::new();
+    let tokens = compilation_unit::lex_lossless(source, 0, &mut diagnostics);
+    if diagnostics.has_errors() {
+        return Err(diagnostics);
+    }
+    Ok(tokens)
+}
+
+pub fn parse(tokens: &[Token]) -> Result<Unit, Diagnostics> {
+    let mut diagnostics = Diagnostics::new();
+    let node = compilation_unit::parse(tokens, &mut diagnostics);
+    if diagnostics.has_errors() {
+        return Err(diagnostics);
+    }
+    Ok(node)
+}
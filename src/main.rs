@@ -1,13 +1,116 @@
 use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::exit;
 
-mod lexer;
-mod compilation_unit;
-mod parser;
+use suco::{Emit, ErrorFormat, Options, SourceMap};
+use suco::Diagnostic;
+use suco::diagnostics::NO_CODE;
+
+const USAGE: &str = "usage: suco <source path | -> [-o <output>] [--emit=tokens|ast|asm|obj|bin] [--error-format=human|json]";
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut input = None;
+    let mut output = None;
+    let mut emit = Emit::Asm;
+    let mut error_format = ErrorFormat::Human;
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => match iter.next() {
+                Some(path) => output = Some(path.clone()),
+                None => return Err("-o expects an output path".to_string())
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            },
+            _ if arg.starts_with("--emit=") => {
+                emit = Emit::from_name(&arg["--emit=".len()..])?;
+            },
+            _ if arg.starts_with("--error-format=") => {
+                error_format = ErrorFormat::from_name(&arg["--error-format=".len()..])?;
+            },
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option '{}'", arg))
+            },
+            _ => {
+                if input.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg))
+                }
+                input = Some(arg.clone());
+            }
+        }
+    }
+    match input {
+        Some(input) => Ok(Options { input, output, emit, error_format }),
+        None => Err("no source path given".to_string())
+    }
+}
+
+// reads the source, writes the artifact and prints the diagnostics the
+// library returned, the library itself never touches files or the terminal
+fn run(options: &Options) -> Result<(), String> {
+    let buffer = read_source(&options.input)?;
+    let result = suco::compile_str(&buffer, options);
+    let diagnostics = match &result {
+        Ok(artifact) => &artifact.diagnostics,
+        Err(diagnostics) => diagnostics
+    };
+    eprint!("{}", suco::render(&buffer, options, diagnostics));
+    let artifact = match result {
+        Ok(artifact) => artifact.bytes,
+        Err(diagnostics) => return Err(format!("could not compile `{}` due to {} previous error(s)",
+                                               options.input, diagnostics.error_count()))
+    };
+
+    let output = options.output_path();
+    if output == "-" {
+        return io::stdout().write_all(&artifact).map_err(|e| format!("cannot write stdout: {}", e));
+    }
+    let mut file = File::create(&output).map_err(|e| format!("cannot create {}: {}", output, e))?;
+    file.write_all(&artifact).map_err(|e| format!("cannot write {}: {}", output, e))?;
+    eprintln!("Compiled to :: Path({}) as :: Output({})\n{} Byte => {} Byte",
+              options.input, output, buffer.len(), artifact.len());
+    Ok(())
+}
+
+fn read_source(path: &str) -> Result<String, String> {
+    let mut buffer = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut buffer).map_err(|e| format!("cannot read stdin: {}", e))?;
+    } else {
+        let mut file = File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+        file.read_to_string(&mut buffer).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    }
+    Ok(buffer)
+}
+
+// errors of the driver itself, they have no code and no location
+fn report(error_format: ErrorFormat, diagnostic: Diagnostic) {
+    let sources = SourceMap::new();
+    match error_format {
+        ErrorFormat::Human => eprint!("{}", diagnostic.render(&sources)),
+        ErrorFormat::Json => eprintln!("{}", diagnostic.to_json(&sources))
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    // if args.len() < 3 {
-    //     panic!("feed some arguments for compiler!\n example: ./suco <source path> <output name>")
-    // }
-    compilation_unit::compilation_unit(args);
-}
\ No newline at end of file
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        // the format is honoured even when other arguments are wrong
+        Err(err) if args.iter().any(|a| a == "--error-format=json") => {
+            report(ErrorFormat::Json, Diagnostic::error(NO_CODE, err).with_note(USAGE.to_string()));
+            exit(2);
+        },
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            exit(2);
+        }
+    };
+    if let Err(err) = run(&options) {
+        report(options.error_format, Diagnostic::error(NO_CODE, err));
+        exit(1);
+    }
+}
@@ -1,9 +1,7 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
-use std::process::exit;
-use crate::lexer::{Token, TokenKind};
-use crate::parser::NodeKind::Param;
+use std::fmt::{Debug, Display, Formatter};
+use crate::lexer::{unescape, Token, TokenKind};
+use crate::diagnostics::{Diagnostic, Diagnostics, E_ARGUMENT_COUNT, E_DUPLICATE_CASE, E_DUPLICATE_NAME, E_EXPECTED_TOKEN, E_IMMUTABLE_ASSIGN, E_INDEX_OUT_OF_BOUNDS, E_LITERAL_OUT_OF_RANGE, E_MISMATCHED_TYPES, E_MISSING_FIELD, E_NOT_LVALUE, E_OUTSIDE_LOOP, E_UNCLOSED_DELIMITER, E_UNEXPECTED_TOKEN, E_UNKNOWN_TYPE, E_UNRESOLVED_NAME, E_UNSUPPORTED_TYPE};
+use crate::source::Span;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum IntType {
@@ -19,6 +17,35 @@ pub enum IntType {
 }
 
 impl IntType {
+    // type named by a literal suffix such as the `u8` in `12u8`
+    pub fn from_name(name: &str) -> Option<IntType> {
+        match name {
+            "i8" => Some(IntType::Int8),
+            "u8" => Some(IntType::Uint8),
+            "i16" => Some(IntType::Int16),
+            "u16" => Some(IntType::Uint16),
+            "i32" => Some(IntType::Int32),
+            "u32" => Some(IntType::Uint32),
+            "i64" => Some(IntType::Int64),
+            "u64" => Some(IntType::Uint64),
+            _ => None
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, IntType::Int8 | IntType::Int16 | IntType::Int32 | IntType::Int64)
+    }
+
+    // whether `n` lies in the value range of the type
+    pub fn fits(&self, n: i64) -> bool {
+        let bits = self.size() * 8;
+        match (bits, self.is_signed()) {
+            (0, _) => n == 0,
+            (_, true) => (-(1i128 << (bits - 1))..1i128 << (bits - 1)).contains(&(n as i128)),
+            (_, false) => (0..1i128 << bits).contains(&(n as i128))
+        }
+    }
+
     pub fn size(&self) -> u32 {
         match self {
             IntType::Uint0 => 0,
@@ -34,44 +61,276 @@ impl IntType {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+impl Display for IntType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntType::Uint0 => write!(f, "u0"),
+            IntType::Int8 => write!(f, "i8"),
+            IntType::Uint8 => write!(f, "u8"),
+            IntType::Int16 => write!(f, "i16"),
+            IntType::Uint16 => write!(f, "u16"),
+            IntType::Int32 => write!(f, "i32"),
+            IntType::Uint32 => write!(f, "u32"),
+            IntType::Int64 => write!(f, "i64"),
+            IntType::Uint64 => write!(f, "u64"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum Types {
     Int(IntType),
+    Pointer(Box<Types>),    // `*T`, `*u0` points to anything and cannot be dereferenced
+    Array(Box<Types>, u32),    // `[T; N]`
+    Struct(String),    // named layout in the unit's `TypeTable`
     Function
 }
 
+impl Display for Types {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Types::Int(int) => write!(f, "{}", int),
+            Types::Pointer(to) => write!(f, "*{}", to),
+            Types::Array(element, count) => write!(f, "[{}; {}]", element, count),
+            Types::Struct(name) => write!(f, "{}", name),
+            Types::Function => write!(f, "function")
+        }
+    }
+}
+
 impl Types {
-    pub fn detect(&self) -> u32 {
+    // size in bytes, struct layouts come from `types`
+    pub fn detect(&self, types: &TypeTable) -> u32 {
         match self {
             Types::Int(int) => int.size(),
+            Types::Pointer(_) => 4,
+            Types::Array(element, count) => element.detect(types).saturating_mul(*count),
+            Types::Struct(name) => types.get(name).map_or(0, |s| s.size),
             Types::Function => 0
         }
     }
+
+    // alignment in memory, an array is aligned like its elements and a
+    // struct like its most aligned field
+    pub fn align(&self, types: &TypeTable) -> u32 {
+        match self {
+            Types::Array(element, _) => element.align(types),
+            Types::Struct(name) => types.get(name).map_or(1, |s| s.align),
+            typ => typ.detect(types).max(1)
+        }
+    }
+
+    // arrays and structs live in memory and are never held in a register
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self, Types::Array(..) | Types::Struct(_))
+    }
+
+    // type a pointer points to, None for everything else
+    pub fn pointee(&self) -> Option<&Types> {
+        match self {
+            Types::Pointer(to) => Some(to),
+            _ => None
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+impl BinaryOp {
+    pub fn from_token(kind: TokenKind) -> Option<BinaryOp> {
+        match kind {
+            TokenKind::Plus => Some(BinaryOp::Add),
+            TokenKind::Minus => Some(BinaryOp::Sub),
+            TokenKind::Star => Some(BinaryOp::Mul),
+            TokenKind::Slash => Some(BinaryOp::Div),
+            TokenKind::Percent => Some(BinaryOp::Rem),
+            TokenKind::Amp => Some(BinaryOp::BitAnd),
+            TokenKind::Pipe => Some(BinaryOp::BitOr),
+            TokenKind::Caret => Some(BinaryOp::BitXor),
+            TokenKind::Shl => Some(BinaryOp::Shl),
+            TokenKind::Shr => Some(BinaryOp::Shr),
+            TokenKind::AndAnd => Some(BinaryOp::And),
+            TokenKind::OrOr => Some(BinaryOp::Or),
+            TokenKind::Same => Some(BinaryOp::Eq),
+            TokenKind::BangEqual => Some(BinaryOp::Ne),
+            TokenKind::Less => Some(BinaryOp::Lt),
+            TokenKind::LessEqual => Some(BinaryOp::Le),
+            TokenKind::Greater => Some(BinaryOp::Gt),
+            TokenKind::GreaterEqual => Some(BinaryOp::Ge),
+            _ => None
+        }
+    }
+
+    // operator of a compound assignment such as `+=`
+    pub fn from_assign(kind: TokenKind) -> Option<BinaryOp> {
+        match kind {
+            TokenKind::PlusEqual => Some(BinaryOp::Add),
+            TokenKind::MinusEqual => Some(BinaryOp::Sub),
+            TokenKind::StarEqual => Some(BinaryOp::Mul),
+            TokenKind::SlashEqual => Some(BinaryOp::Div),
+            TokenKind::PercentEqual => Some(BinaryOp::Rem),
+            TokenKind::AmpEqual => Some(BinaryOp::BitAnd),
+            TokenKind::PipeEqual => Some(BinaryOp::BitOr),
+            TokenKind::CaretEqual => Some(BinaryOp::BitXor),
+            TokenKind::ShlEqual => Some(BinaryOp::Shl),
+            TokenKind::ShrEqual => Some(BinaryOp::Shr),
+            _ => None
+        }
+    }
+
+    // binding power, higher binds tighter, the ordering follows Rust
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 3,
+            BinaryOp::BitOr => 4,
+            BinaryOp::BitXor => 5,
+            BinaryOp::BitAnd => 6,
+            BinaryOp::Shl | BinaryOp::Shr => 7,
+            BinaryOp::Add | BinaryOp::Sub => 8,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 9
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        self.precedence() == 3
+    }
+
+    // `!(a < b)` is `a >= b`, only defined for comparisons
+    pub fn negate(&self) -> BinaryOp {
+        match self {
+            BinaryOp::Eq => BinaryOp::Ne,
+            BinaryOp::Ne => BinaryOp::Eq,
+            BinaryOp::Lt => BinaryOp::Ge,
+            BinaryOp::Le => BinaryOp::Gt,
+            BinaryOp::Gt => BinaryOp::Le,
+            BinaryOp::Ge => BinaryOp::Lt,
+            op => *op
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Sub => write!(f, "-"),
+            BinaryOp::Mul => write!(f, "*"),
+            BinaryOp::Div => write!(f, "/"),
+            BinaryOp::Rem => write!(f, "%"),
+            BinaryOp::BitAnd => write!(f, "&"),
+            BinaryOp::BitOr => write!(f, "|"),
+            BinaryOp::BitXor => write!(f, "^"),
+            BinaryOp::Shl => write!(f, "<<"),
+            BinaryOp::Shr => write!(f, ">>"),
+            BinaryOp::And => write!(f, "&&"),
+            BinaryOp::Or => write!(f, "||"),
+            BinaryOp::Eq => write!(f, "=="),
+            BinaryOp::Ne => write!(f, "!="),
+            BinaryOp::Lt => write!(f, "<"),
+            BinaryOp::Le => write!(f, "<="),
+            BinaryOp::Gt => write!(f, ">"),
+            BinaryOp::Ge => write!(f, ">="),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+    AddrOf,
+    Deref
+}
+
+impl UnaryOp {
+    // `&` and `*` are prefix operators wherever an operand is expected
+    pub fn from_token(kind: TokenKind) -> Option<UnaryOp> {
+        match kind {
+            TokenKind::Minus => Some(UnaryOp::Neg),
+            TokenKind::Bang => Some(UnaryOp::Not),
+            TokenKind::Tilde => Some(UnaryOp::BitNot),
+            TokenKind::Amp => Some(UnaryOp::AddrOf),
+            TokenKind::Star => Some(UnaryOp::Deref),
+            _ => None
+        }
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
+            UnaryOp::BitNot => write!(f, "~"),
+            UnaryOp::AddrOf => write!(f, "&"),
+            UnaryOp::Deref => write!(f, "*"),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum NodeKind {
     Program,
     Annotation,
-    NumberLit(i64),
+    NumberLit(i64, Option<IntType>),    // value and the type named by its suffix, if any
     String(usize),
     Identifier(String),
     Variable(Box<Variable>),
     Param(Vec<Parameter>),
     Scope(Vec<Node>),
-    Function(String, IntType, Box<Node>, Box<Node>),
-    Return(Box<Node>)
+    Function(String, Types, Box<Node>, Box<Node>),
+    Return(Option<Box<Node>>),    // no value in `u0` functions
+    If(Box<Node>, Box<Node>, Option<Box<Node>>),
+    // loops carry their optional label first
+    While(Option<String>, Box<Node>, Box<Node>),
+    Loop(Option<String>, Box<Node>),
+    For(Option<String>, Box<Variable>, Box<Node>, Box<Node>),    // counter with its start value, end bound, body
+    Break(Option<String>),
+    Continue(Option<String>),
+    Switch(Box<Node>, Vec<Case>, Option<Box<Node>>),    // scrutinee, arms, default arm
+    Assign(Option<BinaryOp>, Box<Node>, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Call(String, Vec<Node>),
+    Index(Box<Node>, Box<Node>),    // array or pointer, index
+    ArrayLit(Vec<Node>),
+    ArrayRepeat(Box<Node>, u32),    // `[value; count]`
+    Field(Box<Node>, String),    // a struct, `p->f` is a field of `*p`
+    StructLit(Vec<(String, Node)>)    // fields in source order
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Parameter {
     pub name: String,
-    pub types: IntType,
+    pub types: Types,
     pub content: u32,
 }
 
 impl Parameter {
-    pub fn new(name: String, types: IntType, content: u32) -> Parameter {
+    pub fn new(name: String, types: Types, content: u32) -> Parameter {
         Parameter {
             name,
             types,
@@ -80,123 +339,445 @@ impl Parameter {
     }
 }
 
+// what a call needs to know about a function, collected before any body is
+// parsed so calls can name functions defined further down
+#[derive(PartialEq, Debug, Clone)]
+pub struct Signature {
+    pub name: String,
+    pub returns: Types,
+    pub params: Vec<Parameter>,
+    pub span: Span    // the name in the definition
+}
+
+// one `case a, b => { }` arm, every value is a NumberLit
+#[derive(PartialEq, Debug, Clone)]
+pub struct Case {
+    pub values: Vec<Node>,
+    pub body: Node
+}
+
+impl Case {
+    pub fn new(values: Vec<Node>, body: Node) -> Case {
+        Case {
+            values,
+            body
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Variable {
     pub name: String,
-    pub types: IntType,
-    pub content: i64,
-    pub global: bool
+    pub types: Types,
+    pub content: Node,
+    pub global: bool,
+    pub mutable: bool
 }
 
 impl Variable {
-    pub fn new(name: String, types: IntType, content: i64, global: bool) -> Variable {
+    pub fn new(name: String, types: Types, content: Node, global: bool, mutable: bool) -> Variable {
         Variable {
             name,
             types,
             content,
-            global
+            global,
+            mutable
         }
     }
 }
 
+// a name visible in the function being parsed, `span` is where it was declared
+struct Binding {
+    name: String,
+    typ: Types,
+    mutable: bool,
+    param: bool,
+    counter: bool,    // the counter of a `for` loop
+    span: Span
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Node {
     pub kind: NodeKind,
-    pub typ: Types
+    pub typ: Types,
+    pub span: Span
 }
 
 impl Node {
     pub fn new() -> Self {
         Self {
             kind: NodeKind::Program,                         // default state
-            typ: Types::Int(IntType::Int32)            // default integer type
+            typ: Types::Int(IntType::Int32),           // default integer type
+            span: Span::default()
         }
     }
 
-    fn new_int(val: i64, typ: IntType) -> Self {
+    // `suffix` is the type written on the literal, without one it adapts to its context
+    fn new_int(val: i64, typ: IntType, suffix: Option<IntType>, span: Span) -> Self {
         Self {
-            kind: NodeKind::NumberLit(val),
-            typ: Types::Int(typ)
+            kind: NodeKind::NumberLit(val, suffix),
+            typ: Types::Int(typ),
+            span
         }
     }
 
-    fn new_return(val: Self) -> Self {
+    // `index` points into `Unit::strings`, the value is the address of the bytes
+    fn new_string(index: usize, span: Span) -> Self {
         Self {
-            kind: NodeKind::Return(Box::new(val)),
+            kind: NodeKind::String(index),
+            typ: Types::Int(IntType::Uint32),
+            span
+        }
+    }
+
+    fn new_return(val: Option<Self>, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Return(val.map(Box::new)),
             typ: Types::Int(IntType::Uint0),
+            span
         }
     }
 
-    fn new_identifier(val: String) -> Self {
+    fn new_identifier(val: String, span: Span) -> Self {
         Self {
             kind: NodeKind::Identifier(val),
-            typ: Types::Int(IntType::Uint0)
+            typ: Types::Int(IntType::Uint0),
+            span
+        }
+    }
+
+    fn new_binary(op: BinaryOp, lhs: Self, rhs: Self, typ: Types) -> Self {
+        let span = lhs.span.to(rhs.span);
+        Self {
+            kind: NodeKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+            typ,
+            span
+        }
+    }
+
+    // `otherwise` is a Scope, or another If for `else if`
+    fn new_if(cond: Self, then: Self, otherwise: Option<Self>, span: Span) -> Self {
+        Self {
+            kind: NodeKind::If(Box::new(cond), Box::new(then), otherwise.map(Box::new)),
+            typ: Types::Int(IntType::Uint0),
+            span
+        }
+    }
+
+    fn new_statement(kind: NodeKind, span: Span) -> Self {
+        Self {
+            kind,
+            typ: Types::Int(IntType::Uint0),
+            span
+        }
+    }
+
+    // `op` is set for compound assignments, `x += 1` keeps `x` as the target
+    fn new_assign(op: Option<BinaryOp>, target: Self, value: Self, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Assign(op, Box::new(target), Box::new(value)),
+            typ: Types::Int(IntType::Uint0),
+            span
+        }
+    }
+
+    fn new_unary(op: UnaryOp, operand: Self, typ: Types, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Unary(op, Box::new(operand)),
+            typ,
+            span
+        }
+    }
+
+    // `typ` is the return type of the callee
+    fn new_call(name: String, args: Vec<Node>, typ: Types, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Call(name, args),
+            typ,
+            span
+        }
+    }
+
+    fn new_index(base: Self, index: Self, typ: Types, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Index(Box::new(base), Box::new(index)),
+            typ,
+            span
+        }
+    }
+
+    fn new_field(base: Self, name: String, typ: Types, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Field(Box::new(base), name),
+            typ,
+            span
         }
     }
 
-    fn new_variable(args: Variable) -> Self {
+    // `kind` is an ArrayLit, ArrayRepeat or StructLit
+    fn new_array(kind: NodeKind, typ: Types, span: Span) -> Self {
+        Self {
+            kind,
+            typ,
+            span
+        }
+    }
+
+    fn new_variable(args: Variable, span: Span) -> Self {
         Self {
             kind: NodeKind::Variable(Box::new(args)),
-            typ: Types::Int(IntType::Uint0)
+            typ: Types::Int(IntType::Uint0),
+            span
         }
     }
 
-    fn new_params(args: Vec<Parameter>) -> Self {
+    fn new_params(args: Vec<Parameter>, span: Span) -> Self {
         Self {
             kind: NodeKind::Param(args),
-            typ: Types::Int(IntType::Uint0)
+            typ: Types::Int(IntType::Uint0),
+            span
         }
     }
 
-    fn new_scope(statements: Vec<Node>) -> Self {
+    fn new_scope(statements: Vec<Node>, span: Span) -> Self {
         Self {
             kind: NodeKind::Scope(statements),
-            typ: Types::Int(IntType::Uint0)
+            typ: Types::Int(IntType::Uint0),
+            span
         }
     }
 
-    fn new_function(name: &String, typ: IntType, param: Self, block: Self) -> Self  {
+    fn new_annotation(typ: Types, span: Span) -> Self {
+        Self {
+            kind: NodeKind::Annotation,
+            typ,
+            span
+        }
+    }
+
+    fn new_function(name: &String, typ: Types, param: Self, block: Self, span: Span) -> Self  {
         let name = name.to_string();
         Self {
             kind: NodeKind::Function(name, typ, Box::new(param), Box::new(block)),
-            typ: Types::Function
+            typ: Types::Function,
+            span
+        }
+    }
+
+    // `null`, a zero typed as the pointer the context expects
+    fn new_null(typ: Types, span: Span) -> Self {
+        Self {
+            kind: NodeKind::NumberLit(0, None),
+            typ,
+            span
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.kind, NodeKind::NumberLit(..)) && matches!(self.typ, Types::Pointer(_))
+    }
+
+    // literals without a type suffix (chars included) adapt to their context
+    pub fn is_flexible(&self) -> bool {
+        matches!(self.kind, NodeKind::NumberLit(_, None))
+    }
+
+    // expressions naming memory, they can be assigned to and have an address
+    pub fn is_place(&self) -> bool {
+        match &self.kind {
+            NodeKind::Identifier(_) | NodeKind::Unary(UnaryOp::Deref, _) | NodeKind::Index(..) => true,
+            NodeKind::Field(base, _) => base.is_place(),
+            _ => false
         }
     }
 }
 
-type ParseResult<T> = Result<T, String>;
+type ParseResult<T> = Result<T, Diagnostic>;
+
+// type both operands of a binary operator are converted to, the wider one
+// and unsigned when both have the same width
+pub fn common_type(l: IntType, r: IntType) -> IntType {
+    if r.size() > l.size() || (r.size() == l.size() && !r.is_signed()) {
+        r
+    } else {
+        l
+    }
+}
+
+// integer type of an expression, addresses are `u32` and non integer
+// contexts fall back to `i32`
+pub fn int_type(typ: &Types) -> IntType {
+    match typ {
+        Types::Int(int) => *int,
+        Types::Pointer(_) => IntType::Uint32,
+        Types::Array(..) | Types::Struct(_) | Types::Function => IntType::Int32
+    }
+}
+
+// whether a value of `from` is accepted where `to` is expected, integers
+// convert among themselves but pointers have to match, except for `null`
+pub fn compatible(to: &Types, from: &Node) -> bool {
+    match (to, &from.typ) {
+        (Types::Int(_), Types::Int(_)) => true,
+        (Types::Pointer(_), Types::Pointer(_)) => *to == from.typ || from.is_null(),
+        (Types::Array(..), Types::Array(..)) | (Types::Struct(_), Types::Struct(_)) => *to == from.typ,
+        _ => false
+    }
+}
+
+// a struct field at `offset` bytes from the start of the struct
+#[derive(PartialEq, Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub types: Types,
+    pub offset: u32
+}
+
+// layout of a `struct` item, the same one a C compiler picks: fields in
+// declaration order, each aligned for its type, the size padded to `align`
+#[derive(PartialEq, Debug, Clone)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub size: u32,     // 0 while the fields are still being parsed
+    pub align: u32,
+    pub span: Span
+}
+
+impl Struct {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+// the structs of a unit, filled by the parser and read by codegen
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TypeTable {
+    pub structs: Vec<Struct>
+}
+
+impl TypeTable {
+    pub fn get(&self, name: &str) -> Option<&Struct> {
+        self.structs.iter().find(|s| s.name == name)
+    }
+}
+
+// everything the parser produced for one source file
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Unit {
+    pub items: Vec<Node>,
+    pub strings: Vec<Vec<u8>>,     // string literal bytes, without terminator
+    pub types: TypeTable
+}
+
+// value and suffix type of an integer literal the lexer already validated,
+// the value is None when it does not fit into an i64
+pub fn int_literal(text: &str) -> (Option<i64>, Option<IntType>) {
+    let (radix, body) = match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        _ => (10, text)
+    };
+    let (digits, suffix) = match body.find(['i', 'u']) {
+        Some(at) => body.split_at(at),
+        None => (body, "")
+    };
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    (i64::from_str_radix(&digits, radix).ok(), IntType::from_name(suffix))
+}
+
+// a token as it is quoted in messages, the Eof token has no text to show
+fn describe(token: &Token) -> String {
+    match token.kind {
+        TokenKind::Eof => "end of file".to_string(),
+        _ => format!("`{}`", token.text)
+    }
+}
 
 pub struct Parser<'a> {
-    tokens: &'a Vec<Token>,
+    tokens: &'a [Token],
+    diagnostics: &'a mut Diagnostics,
+    strings: Vec<Vec<u8>>,
+    types: TypeTable,    // structs declared so far
+    functions: Vec<Signature>,    // every function of the unit, in definition order
+    locals: Vec<Binding>,    // parameters and `let`s of the current function
+    loops: Vec<Option<String>>,    // labels of the loops around the current statement
     pos: usize
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a Vec<Token>) -> Self {
+    pub fn new(tokens: &'a [Token], diagnostics: &'a mut Diagnostics) -> Self {
         Self {
             tokens,
+            diagnostics,
+            strings: Vec::new(),
+            types: TypeTable::default(),
+            functions: Vec::new(),
+            locals: Vec::new(),
+            loops: Vec::new(),
             pos: 0
         }
     }
 
-    pub fn parsing_unit(&mut self) -> Vec<Node> {
-        let mut r = Vec::new();
+    // parses every item it can, errors are reported to `diagnostics` and the
+    // parser skips ahead to the next `defun` or `struct`, the structs are
+    // laid out first so every function sees all of them
+    pub fn parsing_unit(&mut self) -> Unit {
+        self.declare_structs();
+        self.parse_items(TokenKind::Struct);
+        self.declare_functions();
+        let items = self.parse_items(TokenKind::Defun);
+        Unit { items, strings: std::mem::take(&mut self.strings), types: std::mem::take(&mut self.types) }
+    }
 
+    // one pass over the file for the items starting with `kind`, the other
+    // items are skipped and stray tokens are reported with the functions
+    fn parse_items(&mut self, kind: TokenKind) -> Vec<Node> {
+        let mut r = Vec::new();
+        let items = [TokenKind::Defun, TokenKind::Struct];
+        self.pos = 0;
         while let Some(token) = self.tokens.get(self.pos) {
-            let chr = token.span.chars().next();
-            if let Some(c) = chr {
-                if !c.is_ascii_punctuation() {
-                    let span = token.span.as_str();
-                    match span {
-                        "defun" => match self.parse_func() {
-                            Ok(d) => r.push(d),
-                            Err(e) => panic!("{:?}", e)
-                        },
-                        "\0" => {},
-                        _ => {
-                            panic!("Unexpected Keyword: {:?}", span);
-                        }
+            match token.kind {
+                TokenKind::Defun if kind == TokenKind::Defun => match self.parse_func() {
+                    Ok(d) => r.push(d),
+                    Err(e) => {
+                        self.report(e);
+                        self.recover(&items);
+                        continue;
+                    }
+                },
+                TokenKind::Struct if kind == TokenKind::Struct => {
+                    let declared = self.tokens.get(self.pos + 1).map(|t| t.span);
+                    if let Err(e) = self.parse_struct() {
+                        // a half parsed struct is forgotten again
+                        self.types.structs.retain(|s| Some(s.span) != declared);
+                        self.report(e);
+                        self.recover(&items);
+                        continue;
                     }
+                },
+                TokenKind::Defun | TokenKind::Struct => {
+                    self.pos += 1;
+                    self.recover(&items);
+                    continue;
+                },
+                TokenKind::Eof => {},
+                _ if kind == TokenKind::Defun => {
+                    let e = self.error(E_UNEXPECTED_TOKEN, format!("expected item, found {}", describe(token)), "expected `defun` or `struct`");
+                    self.report(e);
+                    self.recover(&items);
+                    continue;
+                },
+                _ => {
+                    self.recover(&items);
+                    continue;
                 }
             }
             self.pos += 1;
@@ -204,53 +785,470 @@ impl<'a> Parser<'a> {
         r
     }
 
-    pub fn parse_func(&mut self) -> ParseResult<Node> {
+    // every `struct Name` is known before any fields are parsed, so structs
+    // can point to each other in any order
+    fn declare_structs(&mut self) {
+        let tokens = self.tokens;
+        for pair in tokens.windows(2) {
+            if pair[0].kind == TokenKind::Struct && pair[1].kind == TokenKind::Identifier && self.types.get(&pair[1].text).is_none() {
+                self.types.structs.push(Struct { name: pair[1].text.clone(), fields: Vec::new(), size: 0, align: 1, span: pair[1].span });
+            }
+        }
+    }
+
+    // `struct Name { a: T, b: T }`, a field can point to any struct but only
+    // contain the ones declared before
+    pub fn parse_struct(&mut self) -> ParseResult<()> {
+        let identifier = self.parse_identifier()?;
+        let name = match identifier.kind {
+            NodeKind::Identifier(name) => name,
+            _ => return Err(Diagnostic::error(E_EXPECTED_TOKEN, "expected struct name".to_string())
+                .with_label(identifier.span, "expected identifier".to_string()))
+        };
+        // `declare_structs` registered the first definition of every name
+        let at = match self.types.structs.iter().position(|s| s.name == name) {
+            Some(at) if self.types.structs[at].span == identifier.span => at,
+            Some(first) => {
+                let first = self.types.structs[first].span;
+                return Err(Diagnostic::error(E_DUPLICATE_NAME, format!("the struct `{}` is defined multiple times", name))
+                    .with_label(identifier.span, format!("`{}` redefined here", name))
+                    .with_secondary(first, format!("previous definition of `{}` here", name)));
+            },
+            // the first definition failed and was forgotten
+            None => {
+                self.types.structs.push(Struct { name: name.clone(), fields: Vec::new(), size: 0, align: 1, span: identifier.span });
+                self.types.structs.len() - 1
+            }
+        };
+        self.expect(TokenKind::Lbrace, "{")?;
+        let open = self.span();
+        let mut fields: Vec<Field> = Vec::new();
+        let mut declared: Vec<Span> = Vec::new();
+        let (mut size, mut align) = (0u64, 1);
+        loop {
+            self.pos += 1;
+            let token = match self.tokens.get(self.pos) {
+                Some(t) if t.kind == TokenKind::Rbrace => break,
+                Some(t) if t.kind == TokenKind::Identifier => t,
+                Some(t) if matches!(t.kind, TokenKind::Eof | TokenKind::Defun | TokenKind::Struct) => return Err(self.unclosed(open, "{")),
+                Some(t) => return Err(self.error(E_EXPECTED_TOKEN, format!("expected field name, found {}", describe(t)), "expected a field name")),
+                None => return Err(self.unclosed(open, "{"))
+            };
+            if let Some(n) = fields.iter().position(|f| f.name == token.text) {
+                return Err(Diagnostic::error(E_DUPLICATE_NAME, format!("field `{}` is already declared", token.text))
+                    .with_label(token.span, "field already declared".to_string())
+                    .with_secondary(declared[n], format!("`{}` first declared here", token.text)));
+            }
+            self.expect(TokenKind::Colon, ":")?;
+            let first = self.pos + 1;
+            let types = self.parse_type(false)?;
+            let span = self.tokens[first].span.to(self.span());
+            let bytes = types.detect(&self.types);
+            // the struct itself has no size until its last field, nor has
+            // any struct declared after it
+            if bytes == 0 {
+                let note = if types == Types::Struct(name.clone()) {
+                    format!("a struct cannot contain itself, use a `*{}` instead", name)
+                } else {
+                    format!("only structs declared before `{}` can be contained, a pointer works for any struct", name)
+                };
+                return Err(Diagnostic::error(E_UNSUPPORTED_TYPE, format!("field `{}` has incomplete type `{}`", token.text, types))
+                    .with_label(span, "size not known here".to_string())
+                    .with_note(note));
+            }
+            let field_align = types.align(&self.types);
+            let offset = size.next_multiple_of(field_align as u64);
+            size = offset + bytes as u64;
+            align = align.max(field_align);
+            fields.push(Field { name: token.text.clone(), types, offset: offset as u32 });
+            declared.push(token.span);
+            match self.tokens.get(self.pos + 1) {
+                Some(t) if t.kind == TokenKind::Comma => self.pos += 1,
+                Some(t) if t.kind == TokenKind::Rbrace => {},
+                Some(t) if matches!(t.kind, TokenKind::Eof | TokenKind::Defun | TokenKind::Struct) => {
+                    self.pos += 1;
+                    return Err(self.unclosed(open, "{"));
+                },
+                _ => {
+                    self.pos += 1;
+                    return Err(self.error(E_EXPECTED_TOKEN, format!("expected `,` or `}}`, found {}", describe(&self.tokens[self.pos])), "expected `,` or `}`")
+                        .with_secondary(open, "struct body starts here".to_string()));
+                }
+            }
+        }
+        if fields.is_empty() {
+            return Err(Diagnostic::error(E_UNSUPPORTED_TYPE, format!("struct `{}` has no fields", name))
+                .with_label(identifier.span.to(self.span()), "empty struct".to_string()));
+        }
+        // the size is padded so the fields of array elements stay aligned
+        let size = size.next_multiple_of(align as u64);
+        if size > i32::MAX as u64 {
+            return Err(Diagnostic::error(E_UNSUPPORTED_TYPE, format!("struct `{}` is too large", name))
+                .with_label(identifier.span, "too large for the stack".to_string()));
+        }
+        let layout = &mut self.types.structs[at];
+        layout.fields = fields;
+        layout.size = size as u32;
+        layout.align = align;
+        Ok(())
+    }
+
+    // records the signature of every `defun` before the bodies are parsed,
+    // errors are left for `parse_func` to report
+    fn declare_functions(&mut self) {
+        let mut quiet = Diagnostics::new();
+        std::mem::swap(self.diagnostics, &mut quiet);
+        for at in 0..self.tokens.len() {
+            if self.tokens[at].kind != TokenKind::Defun {
+                continue;
+            }
+            self.pos = at;
+            if let Ok((identifier, parameter, fn_type)) = self.parse_signature() {
+                if let (NodeKind::Identifier(name), NodeKind::Param(params)) = (identifier.kind, parameter.kind) {
+                    self.functions.push(Signature { name, returns: fn_type.typ, params, span: identifier.span });
+                }
+            }
+        }
+        std::mem::swap(self.diagnostics, &mut quiet);
+        self.pos = 0;
+    }
+
+    // `name(params) -> type` after `defun`, ends on the last token of the type
+    fn parse_signature(&mut self) -> ParseResult<(Node, Node, Node)> {
         let identifier = self.parse_identifier()?;
         let parameter = self.parse_params()?;
         let fn_type = self.parse_fn_type()?;
-        let fn_body = self.parse_scope(fn_type.typ.clone())?;
+        Ok((identifier, parameter, fn_type))
+    }
 
+    pub fn parse_func(&mut self) -> ParseResult<Node> {
+        let start = self.span();
+        let (identifier, parameter, fn_type) = self.parse_signature()?;
         let name = match identifier.kind {
             NodeKind::Identifier(a) => a,
-            _ => todo!()
+            _ => return Err(Diagnostic::error(E_EXPECTED_TOKEN, "expected function name".to_string())
+                .with_label(identifier.span, "expected identifier".to_string()))
         };
+        // calls resolve to the first definition, any later one is an error
+        let duplicate = self.functions.iter().find(|f| f.name == name).filter(|f| f.span != identifier.span).map(|first| {
+            Diagnostic::error(E_DUPLICATE_NAME, format!("the function `{}` is defined multiple times", name))
+                .with_label(identifier.span, format!("`{}` redefined here", name))
+                .with_secondary(first.span, format!("previous definition of `{}` here", name))
+        });
+        self.locals.clear();
+        self.loops.clear();
+        if let NodeKind::Param(params) = &parameter.kind {
+            let span = parameter.span;
+            self.locals.extend(params.iter().map(|p| Binding { name: p.name.clone(), typ: p.types.clone(), mutable: false, param: true, counter: false, span }));
+        }
+        if fn_type.typ.is_aggregate() {
+            let what = if let Types::Array(..) = fn_type.typ { "arrays" } else { "structs" };
+            return Err(Diagnostic::error(E_UNSUPPORTED_TYPE, format!("functions cannot return {}", what))
+                .with_label(fn_type.span, "unsupported return type".to_string())
+                .with_note(format!("take a `*{}` to fill in as a parameter instead", fn_type.typ)));
+        }
+        let fn_body = self.parse_scope(&fn_type.typ)?;
+        // the body is still checked so its errors show up as well
+        if let Some(e) = duplicate {
+            return Err(e);
+        }
+
+        let span = start.to(fn_body.span);
+        Ok(Node::new_function(&name, fn_type.typ, parameter, fn_body, span))
+    }
+
+    // `return expr;`, or a bare `return;` in functions returning `u0`
+    pub fn parse_return(&mut self, typ: &Types) -> ParseResult<Node> {
+        let start = self.span();
+        let bare = self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Semi);
+        let nothing = *typ == Types::Int(IntType::Uint0);
+        if bare && !nothing {
+            return Err(Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                .with_label(start, format!("expected a `{}` value after `return`", typ)));
+        }
+        if !bare && nothing {
+            self.pos += 1;
+            return Err(self.error(E_MISMATCHED_TYPES, "mismatched types".to_string(), "functions returning `u0` cannot return a value")
+                .with_secondary(start, "use a bare `return;` here".to_string()));
+        }
+        let expr = if bare { None } else { Some(self.parse_expr(typ)?) };
+        self.consume_semi()?;
+        Ok(Node::new_return(expr, start.to(self.span())))
+    }
+
+    // `if cond { } else if cond { } else { }`, the condition is any integer
+    // expression and true when not zero
+    pub fn parse_if(&mut self, typ: &Types) -> ParseResult<Node> {
+        let start = self.span();
+        let cond = self.parse_binary(&Types::Int(IntType::Int32), 0)?;
+        let then = self.parse_scope(typ)?;
+        let mut otherwise = None;
+        if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Else) {
+            self.pos += 1;
+            if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::If) {
+                self.pos += 1;
+                otherwise = Some(self.parse_if(typ)?);
+            } else {
+                otherwise = Some(self.parse_scope(typ)?);
+            }
+        }
+        Ok(Node::new_if(cond, then, otherwise, start.to(self.span())))
+    }
+
+    // `name: while ...`, the label can be named by `break` and `continue`
+    pub fn parse_labelled(&mut self, typ: &Types) -> ParseResult<Node> {
+        let label = self.tokens[self.pos].text.clone();
+        self.pos += 2;
+        match self.tokens.get(self.pos) {
+            Some(t) if matches!(t.kind, TokenKind::While | TokenKind::Loop | TokenKind::For) => self.parse_loop(Some(label), typ),
+            Some(t) => Err(self.error(E_EXPECTED_TOKEN, format!("expected `while`, `loop` or `for` after a label, found {}", describe(t)), "expected a loop")),
+            None => Err(self.error(E_EXPECTED_TOKEN, "expected a loop after the label".to_string(), "expected a loop"))
+        }
+    }
 
-        let typ = match fn_type.typ {
-            Types::Int(a) => a,
-            _ => todo!()
+    // `while cond { }` and `loop { }`, `for` loops go to `parse_for`
+    pub fn parse_loop(&mut self, label: Option<String>, typ: &Types) -> ParseResult<Node> {
+        let start = self.span();
+        let kind = match self.tokens[self.pos].kind {
+            TokenKind::For => return self.parse_for(label, typ),
+            TokenKind::While => {
+                let cond = self.parse_binary(&Types::Int(IntType::Int32), 0)?;
+                let body = self.parse_loop_body(&label, typ)?;
+                NodeKind::While(label, Box::new(cond), Box::new(body))
+            },
+            _ => NodeKind::Loop(label.clone(), Box::new(self.parse_loop_body(&label, typ)?))
         };
+        Ok(Node::new_statement(kind, start.to(self.span())))
+    }
 
-        Ok(Node::new_function(&name, typ, parameter, fn_body))
+    // `for i in a..b { }` counts from `a` up to `b` excluded, the counter is
+    // immutable and typed by the bounds unless annotated like `for i: u8 in`
+    pub fn parse_for(&mut self, label: Option<String>, typ: &Types) -> ParseResult<Node> {
+        let start = self.span();
+        self.parse_identifier()?;
+        let counter = &self.tokens[self.pos];
+        let mut annotated = None;
+        if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Colon) {
+            self.pos += 2;
+            match self.tokens.get(self.pos) {
+                Some(t) if IntType::from_name(&t.text).is_some() => annotated = IntType::from_name(&t.text),
+                Some(t) if t.kind == TokenKind::Eof => return Err(self.error(E_EXPECTED_TOKEN, "expected a type, found end of file".to_string(), "expected a type")),
+                Some(t) => return Err(self.error(E_UNKNOWN_TYPE, format!("cannot find type `{}`", t.text), "not a type")),
+                None => return Err(self.error(E_EXPECTED_TOKEN, "expected a type".to_string(), "expected a type"))
+            }
+        }
+        self.expect(TokenKind::In, "in")?;
+        let mut from = self.parse_binary(&Types::Int(annotated.unwrap_or(IntType::Int32)), 0)?;
+        self.expect(TokenKind::DotDot, "..")?;
+        let mut to = self.parse_binary(&from.typ.clone(), 0)?;
+        let tp = match annotated {
+            Some(tp) => tp,
+            None => self.unify(&mut from, &mut to)
+        };
+        let outer = self.locals.len();
+        self.locals.push(Binding { name: counter.text.clone(), typ: Types::Int(tp), mutable: false, param: false, counter: true, span: counter.span });
+        let body = self.parse_loop_body(&label, typ);
+        self.locals.truncate(outer);
+        let counter = Variable::new(counter.text.clone(), Types::Int(tp), from, false, false);
+        Ok(Node::new_statement(NodeKind::For(label, Box::new(counter), Box::new(to), Box::new(body?)), start.to(self.span())))
     }
 
-    pub fn parse_return(&mut self, typ: Types) -> ParseResult<Node> {
-        let expr = self.parse_expr(typ)?;
-        let _ = self.consume_semi()?;
-        Ok(Node::new_return(expr))
+    // the loop is innermost for `break` and `continue` inside the body
+    fn parse_loop_body(&mut self, label: &Option<String>, typ: &Types) -> ParseResult<Node> {
+        self.loops.push(label.clone());
+        let body = self.parse_scope(typ);
+        self.loops.pop();
+        body
     }
 
-    pub fn parse_identifier(&mut self) -> ParseResult<Node> {
-        self.pos += 1;
-        if let Some(x) = self.tokens.get(self.pos) {
-            match x {
-                Token { kind: TokenKind::Identifier, .. } => {
-                    match x.span.parse() {
-                        Ok(n) => Ok(Node::new_identifier(n)),
-                        Err(_) => Err(format!("{}", "Missing Expr".to_string()))
+    // `break;`, `continue;` and their labelled forms `break outer;`
+    pub fn parse_jump(&mut self) -> ParseResult<Node> {
+        let token = &self.tokens[self.pos];
+        let mut label = None;
+        if let Some(name) = self.tokens.get(self.pos + 1).filter(|t| t.kind == TokenKind::Identifier) {
+            self.pos += 1;
+            if !self.loops.contains(&Some(name.text.clone())) {
+                return Err(Diagnostic::error(E_UNRESOLVED_NAME, format!("use of undeclared label `{}`", name.text))
+                    .with_label(name.span, "undeclared label".to_string()));
+            }
+            label = Some(name.text.clone());
+        }
+        if self.loops.is_empty() {
+            return Err(Diagnostic::error(E_OUTSIDE_LOOP, format!("`{}` outside of a loop", token.text))
+                .with_label(token.span, format!("cannot `{}` outside of a loop", token.text)));
+        }
+        self.consume_semi()?;
+        let kind = match token.kind {
+            TokenKind::Break => NodeKind::Break(label),
+            _ => NodeKind::Continue(label)
+        };
+        Ok(Node::new_statement(kind, token.span.to(self.span())))
+    }
+
+    // `switch expr { case 1, 2 => { } default => { } }`, arms do not fall
+    // through and every case value is an integer constant used only once
+    pub fn parse_switch(&mut self, typ: &Types) -> ParseResult<Node> {
+        let start = self.span();
+        let value = self.parse_binary(&Types::Int(IntType::Int32), 0)?;
+        self.expect(TokenKind::Lbrace, "{")?;
+        let open = self.span();
+        let mut cases = Vec::new();
+        let mut default: Option<(Node, Span)> = None;
+        let mut seen: Vec<(i64, Span)> = Vec::new();
+        loop {
+            self.pos += 1;
+            let arm = match self.tokens.get(self.pos) {
+                Some(arm) => arm,
+                None => return Err(self.unclosed(open, "{"))
+            };
+            match arm.kind {
+                TokenKind::Rbrace => break,
+                TokenKind::Case => {
+                    let mut values = Vec::new();
+                    loop {
+                        let case = self.parse_binary(&value.typ, 0)?;
+                        // bad values are reported without giving up on the switch
+                        match case.kind {
+                            NodeKind::NumberLit(n, _) => match seen.iter().find(|(m, _)| *m == n) {
+                                Some((_, first)) => {
+                                    let e = Diagnostic::error(E_DUPLICATE_CASE, format!("duplicate case value `{}`", n))
+                                        .with_label(case.span, "duplicate case".to_string())
+                                        .with_secondary(*first, "first used here".to_string());
+                                    self.report(e);
+                                },
+                                None => {
+                                    seen.push((n, case.span));
+                                    values.push(case);
+                                }
+                            },
+                            _ => self.report(Diagnostic::error(E_EXPECTED_TOKEN, "case values must be integer constants".to_string())
+                                .with_label(case.span, "not a constant".to_string()))
+                        }
+                        if !self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Comma) {
+                            break;
+                        }
+                        self.pos += 1;
                     }
+                    self.expect(TokenKind::FatArrow, "=>")?;
+                    cases.push(Case::new(values, self.parse_scope(typ)?));
                 },
-                _ => Err(format!("Unexpected Token {}", x.span))
+                TokenKind::Default => {
+                    self.expect(TokenKind::FatArrow, "=>")?;
+                    let body = self.parse_scope(typ)?;
+                    match &default {
+                        Some((_, first)) => {
+                            let e = Diagnostic::error(E_DUPLICATE_CASE, "multiple `default` arms in switch".to_string())
+                                .with_label(arm.span, "second `default`".to_string())
+                                .with_secondary(*first, "first `default` here".to_string());
+                            self.report(e);
+                        },
+                        None => default = Some((body, arm.span))
+                    }
+                },
+                TokenKind::Eof | TokenKind::Defun | TokenKind::Struct => return Err(self.unclosed(open, "{")),
+                _ => return Err(self.error(E_UNEXPECTED_TOKEN, format!("expected `case`, `default` or `}}`, found {}", describe(arm)), "expected a switch arm"))
             }
-        } else {
-            Err(format!("{}", "Missing Token".to_string()))
+        }
+        let default = default.map(|(body, _)| Box::new(body));
+        Ok(Node::new_statement(NodeKind::Switch(Box::new(value), cases, default), start.to(self.span())))
+    }
+
+    // `f(a, b);`, the result is discarded
+    pub fn parse_call_statement(&mut self) -> ParseResult<Node> {
+        let call = self.parse_call(&self.tokens[self.pos])?;
+        self.consume_semi()?;
+        Ok(call)
+    }
+
+    // `x = expr;`, `*p = expr;` and the compound forms such as `x += expr;`
+    pub fn parse_assign(&mut self) -> ParseResult<Node> {
+        // the target is an expression starting at the current token
+        self.pos -= 1;
+        let target = self.parse_unary(&Types::Int(IntType::Int32))?;
+        self.pos += 1;
+        let op = match self.tokens.get(self.pos) {
+            Some(t) if t.kind == TokenKind::Equal => None,
+            Some(t) => match BinaryOp::from_assign(t.kind) {
+                Some(op) => Some(op),
+                None => return Err(self.error(E_EXPECTED_TOKEN, format!("expected `=`, found {}", describe(t)), "expected an assignment operator"))
+            },
+            None => return Err(self.error(E_EXPECTED_TOKEN, "expected `=`".to_string(), "expected an assignment operator"))
+        };
+        // an element of an array or a field of a struct belongs to the variable
+        let mut root = &target;
+        loop {
+            match &root.kind {
+                NodeKind::Index(base, _) if matches!(base.typ, Types::Array(..)) => root = base,
+                NodeKind::Field(base, _) if matches!(base.typ, Types::Struct(_)) => root = base,
+                _ => break
+            }
+        }
+        match &root.kind {
+            NodeKind::Identifier(name) => {
+                let binding = self.locals.iter().rev().find(|b| b.name == *name).map(|b| (b.mutable, b.param, b.counter, b.span));
+                if let Some((false, param, counter, declared)) = binding {
+                    let assign = target.span.to(self.span());
+                    if param {
+                        return Err(Diagnostic::error(E_IMMUTABLE_ASSIGN, format!("cannot assign to immutable argument `{}`", name))
+                            .with_label(assign, "cannot assign to immutable argument".to_string()));
+                    }
+                    let at = Span::new(declared.file, declared.start, declared.start);
+                    let e = if root == &target {
+                        Diagnostic::error(E_IMMUTABLE_ASSIGN, format!("cannot assign twice to immutable variable `{}`", name))
+                            .with_label(assign, "cannot assign twice to immutable variable".to_string())
+                            .with_secondary(declared, format!("first assignment to `{}`", name))
+                    } else {
+                        let part = if let NodeKind::Field(..) = target.kind { "a field" } else { "an element" };
+                        Diagnostic::error(E_IMMUTABLE_ASSIGN, format!("cannot assign to {} of immutable variable `{}`", part, name))
+                            .with_label(assign, format!("cannot assign to {} of an immutable variable", part))
+                            .with_secondary(declared, format!("`{}` is declared here", name))
+                    };
+                    // `for mut i` does not exist, so there is nothing to suggest
+                    if counter {
+                        return Err(e.with_note("loop counters are immutable".to_string()));
+                    }
+                    return Err(e.with_suggestion(at, "mut ".to_string(), format!("consider making this binding mutable: `mut {}`", name)));
+                }
+            },
+            // the pointee of any pointer can be written
+            _ if root.is_place() => {},
+            _ => return Err(Diagnostic::error(E_NOT_LVALUE, "invalid left-hand side of assignment".to_string())
+                .with_label(target.span, "cannot assign to this expression".to_string()))
+        }
+        // `p += n` moves a pointer by `n` elements
+        let hint = match (op, &target.typ) {
+            (Some(_), Types::Pointer(_)) => Types::Int(IntType::Int32),
+            _ => target.typ.clone()
+        };
+        let mut value = self.parse_expr(&hint)?;
+        if let Some(op) = op {
+            let result = self.binary_type(op, &mut target.clone(), &mut value, &hint)?;
+            let pointers = matches!(target.typ, Types::Pointer(_)) || matches!(result, Types::Pointer(_));
+            if pointers && result != target.typ {
+                return Err(Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                    .with_label(target.span.to(value.span), format!("expected `{}`, found `{}`", target.typ, result)));
+            }
+        }
+        self.consume_semi()?;
+        let span = target.span.to(self.span());
+        Ok(Node::new_assign(op, target, value, span))
+    }
+
+    pub fn parse_identifier(&mut self) -> ParseResult<Node> {
+        self.pos += 1;
+        match self.tokens.get(self.pos) {
+            Some(x) if x.kind == TokenKind::Identifier => Ok(Node::new_identifier(x.text.clone(), x.span)),
+            Some(x) => Err(self.error(E_EXPECTED_TOKEN, format!("expected identifier, found {}", describe(x)), "expected identifier")),
+            None => Err(self.error(E_EXPECTED_TOKEN, "expected identifier".to_string(), "expected identifier"))
         }
     }
 
     pub fn parse_params(&mut self) -> ParseResult<Node> {
         self.pos += 1;
+        let start = self.span();
         if let Some(p) = self.tokens.get(self.pos) {
             if p.kind != TokenKind::Lparen {
-                return Err(format!("Expected ( but found::{}", p.span))
+                return Err(self.error(E_EXPECTED_TOKEN, format!("expected `(`, found {}", describe(p)), "expected `(`"))
             }
             self.pos += 1;
         }
@@ -261,84 +1259,77 @@ impl<'a> Parser<'a> {
                 TokenKind::Comma => {},
                 TokenKind::Identifier => {
                     let mut name = String::new();
-                    name.push_str(i.span.as_str());
+                    name.push_str(i.text.as_str());
                     self.pos += 1;
                     if let Some(colon) = self.tokens.get(self.pos) {
                         if colon.kind == TokenKind::Colon {
-                            self.pos += 1;
-                            if let Some(t) = self.tokens.get(self.pos) {
-                                params.push(Parameter::new(name, match t.kind {
-                                    TokenKind::Uint8 => IntType::Uint8,
-                                    TokenKind::Int8 => IntType::Int8,
-                                    TokenKind::Uint16 => IntType::Uint16,
-                                    TokenKind::Int16 => IntType::Int16,
-                                    TokenKind::Uint32 => IntType::Uint32,
-                                    TokenKind::Int32 => IntType::Int32,
-                                    _ => return Err(format!("Type Not Exist Error AT Token: {} Number: {}", t.span, self.pos))
-                                }, 0))
+                            let types = self.parse_type(false)?;
+                            match &types {
+                                Types::Array(element, _) => return Err(self.error(E_UNSUPPORTED_TYPE, format!("parameter `{}` cannot be an array", name), "arrays are not passed by value")
+                                    .with_note(format!("pass a `*{}` pointing to the first element instead", element))),
+                                Types::Struct(_) => return Err(self.error(E_UNSUPPORTED_TYPE, format!("parameter `{}` cannot be a struct", name), "structs are not passed by value")
+                                    .with_note(format!("pass a `*{}` instead", types))),
+                                _ => {}
                             }
+                            // arguments are passed as dwords
+                            if types.detect(&self.types) > 4 {
+                                return Err(self.error(E_UNKNOWN_TYPE, format!("cannot find type `{}`", types), "not a parameter type"));
+                            }
+                            params.push(Parameter::new(name, types, 0));
                         } else {
-                            return Err(format!("Unexpected Token AT Token After: {} Number: {} expected :", i.span, self.pos))
+                            return Err(self.error(E_EXPECTED_TOKEN, format!("expected `:`, found {}", describe(colon)), "expected `:`")
+                                .with_note(format!("parameter `{}` needs a type annotation", name)))
                         }
                     }
                 },
-                _ => return Err(format!("Unexpected Token AT Token: {} Number: {}", i.span, self.pos))
+                TokenKind::Eof => return Err(self.unclosed(start, "(")),
+                _ => return Err(self.error(E_UNEXPECTED_TOKEN, format!("unexpected token `{}` in parameter list", i.text), "unexpected token"))
             }
             self.pos += 1;
         }
-        Ok(Node::new_params(params))
+        Ok(Node::new_params(params, start.to(self.span())))
     }
 
     pub fn parse_variable(&mut self) -> ParseResult<Node> {
+        let start = self.span();
         self.pos += 1;
         let mut name = String::new();
-        let mut tp = IntType::Int32;
-        let mut expr = Node::new_int(0, IntType::Int32);
+        let mut tp = Types::Int(IntType::Int32);
+        let mut expr = Node::new_int(0, IntType::Int32, None, start);
+        let mutable = self.tokens.get(self.pos).is_some_and(|t| t.kind == TokenKind::Mut);
+        if mutable {
+            self.pos += 1;
+        }
         if let Some(id) = self.tokens.get(self.pos) {
             match id.kind {
                 TokenKind::Identifier => {
                     self.pos += 1;
-                    name.push_str(id.span.as_str());
+                    name.push_str(id.text.as_str());
                     if let Some(t) = self.tokens.get(self.pos) {
                         if t.kind == TokenKind::Colon {
-                            self.pos += 1;
-                            if let Some(kind) = self.tokens.get(self.pos) {
-                                tp = match kind {
-                                    Token { kind: TokenKind::Int8, .. } => IntType::Int8,
-                                    Token { kind: TokenKind::Uint8, .. } => IntType::Uint8,
-                                    Token { kind: TokenKind::Int16, .. } => IntType::Int16,
-                                    Token { kind: TokenKind::Uint16, .. } => IntType::Uint16,
-                                    Token { kind: TokenKind::Int32, .. } => IntType::Int32,
-                                    Token { kind: TokenKind::Uint32, .. } => IntType::Uint32,
-                                    Token { kind: TokenKind::Int64, .. } => IntType::Int64,
-                                    Token { kind: TokenKind::Uint64, .. } => IntType::Uint64,
-                                    _ => return Err(format!("{} Type Not Exist Error AT Token {}", kind.span, self.pos))
-                                };
-                            }
+                            tp = self.parse_type(false)?;
                             self.pos += 1;
                             if let Some(e) = self.tokens.get(self.pos) {
                                 if e.kind == TokenKind::Equal {
-                                    expr = self.parse_expr(Types::Int(tp))?;
+                                    expr = self.parse_expr(&tp)?;
                                 } else {
-                                    return Err(format!("Expected = but found {} Error AT Token {}", e.span, self.pos))
+                                    return Err(self.error(E_EXPECTED_TOKEN, format!("expected `=`, found {}", describe(e)), "expected `=`"))
                                 }
                             }
-                            let _ = self.consume_semi()?;
+                            self.consume_semi()?;
+                            self.locals.push(Binding { name: name.clone(), typ: tp.clone(), mutable, param: false, counter: false, span: id.span });
                         } else {
-                            return Err(format!("Expected : AT Token: {} But Found {}", self.pos, t.span))
+                            return Err(self.error(E_EXPECTED_TOKEN, format!("expected `:`, found {}", describe(t)), "expected `:`")
+                                .with_note(format!("variable `{}` needs a type annotation", name)))
                         }
                     } else {
-                        return Err(format!("Put Some Type Annotation after variable identifier AT Token: {}", id.span))
+                        return Err(self.error(E_EXPECTED_TOKEN, "expected `:`".to_string(), "expected type annotation"))
                     }
                 }
-                _ => return Err(format!("Expected Name After let Keyword AT Token: {} but Found {}", self.pos, id.span))
+                _ => return Err(self.error(E_EXPECTED_TOKEN, format!("expected identifier, found {}", describe(id)), "expected a name after `let`"))
             }
         }
-        let number = match expr.kind {
-            NodeKind::NumberLit(n) => n,
-            _ => 0
-        };
-        Ok(Node::new_variable(Variable::new(name, tp, number, false)))
+        Ok(Node::new_variable(Variable::new(name, tp, expr, false, mutable), start.to(self.span())))
     }
 
     pub fn parse_fn_type(&mut self) -> ParseResult<Node> {
@@ -346,81 +1337,649 @@ impl<'a> Parser<'a> {
         if let Some(ar) = self.tokens.get(self.pos) {
             match ar {
                 Token { kind: TokenKind::Arrow, .. } => {
-                    self.pos += 1;
-                    let ret = if let Some(a) = self.tokens.get(self.pos) {
-                        let typ = match a {
-                            Token { kind: TokenKind::Int8, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Int8) }),
-                            Token { kind: TokenKind::Uint8, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Uint8) }),
-                            Token { kind: TokenKind::Int16, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Int16) }),
-                            Token { kind: TokenKind::Uint16, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Uint16) }),
-                            Token { kind: TokenKind::Int32, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Int32) }),
-                            Token { kind: TokenKind::Uint32, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Uint32) }),
-                            Token { kind: TokenKind::Int64, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Int64) }),
-                            Token { kind: TokenKind::Uint64, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Uint64) }),
-                            Token { kind: TokenKind::Uint0, .. } => Ok(Node { kind: NodeKind::Annotation, typ: Types::Int(IntType::Uint0) }),
-                            _ => return Err(format!("Unexpected Type Token {}", a.span))
-                        };
-                        typ
-                    } else {Err("".to_string())};
-                    ret
+                    if self.tokens.get(self.pos + 1).is_none() {
+                        return Err(self.error(E_EXPECTED_TOKEN, "expected return type after `->`".to_string(), "expected return type"));
+                    }
+                    let start = self.pos + 1;
+                    let typ = self.parse_type(true)?;
+                    Ok(Node::new_annotation(typ, self.tokens[start].span.to(self.span())))
                 }
-                _ => return Err(format!("Unexpected Token {}", ar.span))
+                _ => Err(self.error(E_EXPECTED_TOKEN, format!("expected `->`, found {}", describe(ar)), "expected `->` and return type"))
             }
-        } else {Err("".to_string())}
+        } else {
+            Err(self.error(E_EXPECTED_TOKEN, "expected `->`".to_string(), "expected `->` and return type"))
+        }
+    }
+
+    // `i32`, `*u8`, `[u8; 16]`, `*[i16; 4]` or a struct name, ends on the last
+    // token of the type, `u0` is only a type behind `->` or a pointer
+    fn parse_type(&mut self, void: bool) -> ParseResult<Types> {
+        self.pos += 1;
+        match self.tokens.get(self.pos) {
+            Some(t) if t.kind == TokenKind::Star => Ok(Types::Pointer(Box::new(self.parse_type(true)?))),
+            Some(t) if t.kind == TokenKind::Lbracket => {
+                let open = t.span;
+                let element = self.parse_type(false)?;
+                self.expect(TokenKind::Semi, ";")?;
+                let count = self.parse_length()?;
+                self.expect(TokenKind::Rbracket, "]")?;
+                // the whole array has to fit into a stack frame, parsing goes
+                // on since the type itself is well formed
+                if element.detect(&self.types) as u64 * count as u64 > i32::MAX as u64 {
+                    let e = Diagnostic::error(E_UNSUPPORTED_TYPE, format!("array type `[{}; {}]` is too large", element, count))
+                        .with_label(open.to(self.span()), "too large for the stack".to_string());
+                    self.report(e);
+                }
+                Ok(Types::Array(Box::new(element), count))
+            },
+            Some(t) if t.kind == TokenKind::Uint0 && void => Ok(Types::Int(IntType::Uint0)),
+            Some(t) if t.kind == TokenKind::Identifier && self.types.get(&t.text).is_some() => Ok(Types::Struct(t.text.clone())),
+            Some(t) if t.kind == TokenKind::Eof => Err(self.error(E_EXPECTED_TOKEN, "expected a type, found end of file".to_string(), "expected a type")),
+            Some(t) => match IntType::from_name(&t.text) {
+                Some(int) => Ok(Types::Int(int)),
+                None => Err(self.error(E_UNKNOWN_TYPE, format!("cannot find type `{}`", t.text), "not a type"))
+            },
+            None => Err(self.error(E_EXPECTED_TOKEN, "expected a type".to_string(), "expected a type"))
+        }
     }
 
-    pub fn parse_scope(&mut self, typ: Types) -> ParseResult<Node> {
+    // statement errors are reported here and parsing resumes after the next `;`
+    pub fn parse_scope(&mut self, typ: &Types) -> ParseResult<Node> {
         self.pos += 1;
+        let start = self.span();
         let mut statements = Vec::new();
+        let outer = self.locals.len();
         match self.tokens.get(self.pos) {
             Some(x) if x.kind == TokenKind::Lbrace => {
                 self.pos += 1;
                 while let Some(stat) = self.tokens.get(self.pos) {
                     if stat.kind == TokenKind::Rbrace {break}
-                    match stat.kind {
-                        TokenKind::Let => {
-                            statements.push(self.parse_variable()?);
-                            self.pos += 1;
-                        }
-                        TokenKind::Return => {
-                            statements.push(self.parse_return(typ)?);
+                    let parsed = match stat.kind {
+                        TokenKind::Let => self.parse_variable(),
+                        TokenKind::Return => self.parse_return(typ),
+                        TokenKind::Identifier if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Colon) => self.parse_labelled(typ),
+                        TokenKind::Identifier if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Lparen) => self.parse_call_statement(),
+                        TokenKind::Identifier | TokenKind::Star | TokenKind::Lparen => self.parse_assign(),
+                        TokenKind::If => self.parse_if(typ),
+                        TokenKind::While | TokenKind::Loop | TokenKind::For => self.parse_loop(None, typ),
+                        TokenKind::Break | TokenKind::Continue => self.parse_jump(),
+                        TokenKind::Switch => self.parse_switch(typ),
+                        TokenKind::Eof | TokenKind::Defun | TokenKind::Struct => return Err(self.unclosed(start, "{")),
+                        _ => Err(self.error(E_UNEXPECTED_TOKEN, format!("expected statement, found {}", describe(stat)), "expected a statement"))
+                    };
+                    match parsed {
+                        Ok(statement) => {
+                            statements.push(statement);
                             self.pos += 1;
                         },
-                        _ => return Err(format!("Unexpected Token {:?} AT Token: {}", stat.span, self.pos))
+                        Err(e) => {
+                            self.report(e);
+                            self.recover(&[TokenKind::Semi, TokenKind::Rbrace]);
+                            if self.tokens.get(self.pos).is_some_and(|t| t.kind == TokenKind::Semi) {
+                                self.pos += 1;
+                            }
+                        }
                     }
                 }
             }
-            _ => return Err("Scope is empty".to_string())
+            Some(x) => return Err(self.error(E_EXPECTED_TOKEN, format!("expected `{{`, found {}", describe(x)), "expected a block")),
+            None => return Err(self.error(E_EXPECTED_TOKEN, "expected `{`".to_string(), "expected a block"))
         };
-        Ok(Node::new_scope(statements))
+        // names declared in the block go out of scope with it
+        self.locals.truncate(outer);
+        Ok(Node::new_scope(statements, start.to(self.span())))
     }
 
-    pub fn parse_expr(&mut self, typ: Types) -> ParseResult<Node> {
+    // precedence climbing over `BinaryOp::precedence`, `typ` is the type the
+    // context expects and what unsuffixed literals default to
+    pub fn parse_expr(&mut self, typ: &Types) -> ParseResult<Node> {
+        let expr = self.parse_binary(typ, 0)?;
+        // a lone suffixed literal has to agree with the context
+        if let (Types::Int(expected), NodeKind::NumberLit(n, Some(suffix))) = (typ, &expr.kind) {
+            if expected != suffix {
+                let error = Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                    .with_label(expr.span, format!("expected `{}`, found `{}`", expected, suffix));
+                // the suffix ends the literal, a value out of range for `expected` gets no suggestion
+                if !expected.fits(*n) {
+                    return Err(error);
+                }
+                let at = Span::new(expr.span.file, expr.span.end - suffix.to_string().len(), expr.span.end);
+                return Err(error.with_suggestion(at, expected.to_string(), format!("change the suffix to `{}`", expected)));
+            }
+        }
+        if !compatible(typ, &expr) {
+            let found = if expr.is_null() { "`null`".to_string() } else { format!("`{}`", expr.typ) };
+            return Err(Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                .with_label(expr.span, format!("expected `{}`, found {}", typ, found)));
+        }
+        Ok(expr)
+    }
+
+    // operators binding tighter than `min` are folded into the left operand,
+    // so equal precedence associates to the left
+    fn parse_binary(&mut self, typ: &Types, min: u8) -> ParseResult<Node> {
+        let mut lhs = self.parse_unary(typ)?;
+        let mut compared: Option<Span> = None;
+        while let Some(token) = self.tokens.get(self.pos + 1) {
+            let op = match BinaryOp::from_token(token.kind) {
+                Some(op) if op.precedence() > min => op,
+                _ => break
+            };
+            self.pos += 1;
+            if op.is_comparison() {
+                if let Some(first) = compared {
+                    return Err(self.error(E_UNEXPECTED_TOKEN, "comparison operators cannot be chained".to_string(), "second comparison")
+                        .with_secondary(first, "first comparison".to_string())
+                        .with_note("split the comparison in two and join them with `&&`".to_string()));
+                }
+                compared = Some(token.span);
+            } else {
+                compared = None;
+            }
+            let hint = match (op, &lhs.typ) {
+                (BinaryOp::And | BinaryOp::Or, _) => typ.clone(),
+                // the offset added to a pointer is an integer
+                (_, Types::Pointer(_) | Types::Array(..)) if !op.is_comparison() => Types::Int(IntType::Int32),
+                (_, lhs) => lhs.clone()
+            };
+            let mut rhs = self.parse_binary(&hint, op.precedence())?;
+            let result = self.binary_type(op, &mut lhs, &mut rhs, typ)?;
+            lhs = Node::new_binary(op, lhs, rhs, result);
+        }
+        Ok(lhs)
+    }
+
+    // result type of `lhs op rhs`, pointers are only compared, moved by an
+    // integer number of elements with `+` and `-`, or subtracted from each other
+    fn binary_type(&self, op: BinaryOp, lhs: &mut Node, rhs: &mut Node, typ: &Types) -> ParseResult<Types> {
+        let (l, r) = (lhs.typ.clone(), rhs.typ.clone());
+        let result = match (op, &l, &r) {
+            (BinaryOp::And | BinaryOp::Or, _, _) => Some(Types::Int(int_type(typ))),
+            (BinaryOp::Shl | BinaryOp::Shr, Types::Int(int), Types::Int(_)) => Some(Types::Int(*int)),
+            (_, Types::Int(_), Types::Int(_)) if op.is_comparison() => {
+                self.unify(lhs, rhs);
+                Some(Types::Int(int_type(typ)))
+            },
+            (_, Types::Int(_), Types::Int(_)) => Some(Types::Int(self.unify(lhs, rhs))),
+            (_, Types::Pointer(_), Types::Pointer(_)) if op.is_comparison() && (compatible(&l, rhs) || compatible(&r, lhs)) => Some(Types::Int(int_type(typ))),
+            (BinaryOp::Add | BinaryOp::Sub, Types::Pointer(to), Types::Int(_)) if to.detect(&self.types) > 0 => Some(l.clone()),
+            (BinaryOp::Add, Types::Int(_), Types::Pointer(to)) if to.detect(&self.types) > 0 => Some(r.clone()),
+            (BinaryOp::Sub, Types::Pointer(to), Types::Pointer(_)) if l == r && to.detect(&self.types) > 0 => Some(Types::Int(IntType::Int32)),
+            _ => None
+        };
+        result.ok_or_else(|| Diagnostic::error(E_MISMATCHED_TYPES, format!("cannot apply `{}` to `{}` and `{}`", op, l, r))
+            .with_label(lhs.span.to(rhs.span), "unsupported operand types".to_string()))
+    }
+
+    fn parse_unary(&mut self, typ: &Types) -> ParseResult<Node> {
+        let token = match self.tokens.get(self.pos + 1) {
+            Some(token) => token,
+            None => return self.parse_postfix(typ)
+        };
+        let op = match UnaryOp::from_token(token.kind) {
+            Some(op) => op,
+            None => return self.parse_postfix(typ)
+        };
+        self.pos += 1;
+        let hint = match op {
+            UnaryOp::AddrOf => typ.pointee().cloned().unwrap_or(Types::Int(IntType::Int32)),
+            UnaryOp::Deref => Types::Pointer(Box::new(typ.clone())),
+            _ => typ.clone()
+        };
+        let operand = self.parse_unary(&hint)?;
+        let span = token.span.to(operand.span);
+        // `-128` is folded so the literal is range checked with its sign
+        if let (UnaryOp::Neg, NodeKind::NumberLit(n, suffix), Types::Int(_)) = (op, &operand.kind, &operand.typ) {
+            return Ok(Node { kind: NodeKind::NumberLit(-n, *suffix), span, ..operand });
+        }
+        let result = match (op, &operand.typ) {
+            (UnaryOp::Not, Types::Int(_) | Types::Pointer(_)) => Types::Int(int_type(typ)),
+            (UnaryOp::Neg | UnaryOp::BitNot, Types::Int(_)) => operand.typ.clone(),
+            (UnaryOp::Deref, Types::Pointer(to)) if to.detect(&self.types) > 0 => (**to).clone(),
+            (UnaryOp::Deref, _) => return Err(Diagnostic::error(E_MISMATCHED_TYPES, format!("type `{}` cannot be dereferenced", operand.typ))
+                .with_label(operand.span, "not a pointer to a value".to_string())),
+            (UnaryOp::AddrOf, _) if operand.is_place() => Types::Pointer(Box::new(operand.typ.clone())),
+            (UnaryOp::AddrOf, _) => return Err(Diagnostic::error(E_NOT_LVALUE, "cannot take the address of a temporary value".to_string())
+                .with_label(operand.span, "not a variable or a dereferenced pointer".to_string())),
+            _ => return Err(Diagnostic::error(E_MISMATCHED_TYPES, format!("cannot apply unary `{}` to `{}`", op, operand.typ))
+                .with_label(span, "unsupported operand type".to_string()))
+        };
+        Ok(Node::new_unary(op, operand, result, span))
+    }
+
+    // `a[i]` indexes arrays and pointers, `s.f` and `p->f` select struct
+    // fields, all binding tighter than prefix operators
+    fn parse_postfix(&mut self, typ: &Types) -> ParseResult<Node> {
+        let mut base = self.parse_primary(typ)?;
+        loop {
+            match self.tokens.get(self.pos + 1).map(|t| t.kind) {
+                Some(TokenKind::Lbracket) => {},
+                Some(TokenKind::Dot | TokenKind::Arrow) => {
+                    base = self.parse_field(base)?;
+                    continue;
+                },
+                _ => break
+            }
+            self.pos += 1;
+            let open = self.span();
+            let index = self.parse_binary(&Types::Int(IntType::Int32), 0)?;
+            if !self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Rbracket) {
+                self.pos += 1;
+                return Err(self.unclosed(open, "["));
+            }
+            self.pos += 1;
+            if !matches!(index.typ, Types::Int(_)) {
+                return Err(Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                    .with_label(index.span, format!("expected an integer index, found `{}`", index.typ)));
+            }
+            let element = match &base.typ {
+                Types::Array(element, count) => {
+                    if !base.is_place() {
+                        return Err(Diagnostic::error(E_NOT_LVALUE, "cannot index a temporary array".to_string())
+                            .with_label(base.span, "not stored in a variable".to_string()));
+                    }
+                    // constant indexes are checked against the length
+                    if let NodeKind::NumberLit(n, _) = index.kind {
+                        if n < 0 || n >= *count as i64 {
+                            return Err(Diagnostic::error(E_INDEX_OUT_OF_BOUNDS, format!("index out of bounds: the length is {} but the index is {}", count, n))
+                                .with_label(index.span, "index out of bounds".to_string()));
+                        }
+                    }
+                    (**element).clone()
+                },
+                Types::Pointer(to) if to.detect(&self.types) > 0 => (**to).clone(),
+                _ => return Err(Diagnostic::error(E_MISMATCHED_TYPES, format!("cannot index into a value of type `{}`", base.typ))
+                    .with_label(base.span, "not an array or a pointer".to_string()))
+            };
+            let span = base.span.to(self.span());
+            base = Node::new_index(base, index, element, span);
+        }
+        Ok(base)
+    }
+
+    // `.f` or `->f` after `base`, a pointer to a struct is dereferenced for
+    // both, so `p.f` and `p->f` are the same field of `*p`
+    fn parse_field(&mut self, base: Node) -> ParseResult<Node> {
+        self.pos += 1;
+        let arrow = self.tokens[self.pos].kind == TokenKind::Arrow;
+        let operator = self.tokens[self.pos].text.clone();
+        let name = match self.tokens.get(self.pos + 1) {
+            Some(t) if t.kind == TokenKind::Identifier => {
+                self.pos += 1;
+                t
+            },
+            Some(t) => return Err(Diagnostic::error(E_EXPECTED_TOKEN, format!("expected field name, found {}", describe(t)))
+                .with_label(t.span, format!("expected a field name after `{}`", operator))),
+            None => return Err(self.error(E_EXPECTED_TOKEN, "expected field name".to_string(), "expected a field name"))
+        };
+        let base = match &base.typ {
+            Types::Pointer(to) if matches!(**to, Types::Struct(_)) => {
+                let span = base.span;
+                let to = (**to).clone();
+                Node::new_unary(UnaryOp::Deref, base, to, span)
+            },
+            Types::Struct(_) if arrow => return Err(Diagnostic::error(E_MISMATCHED_TYPES, format!("`->` on a value of type `{}`", base.typ))
+                .with_label(base.span, "not a pointer".to_string())
+                .with_suggestion(self.tokens[self.pos - 1].span, ".".to_string(), "use `.` to access a field of a struct".to_string())),
+            Types::Struct(_) if !base.is_place() => return Err(Diagnostic::error(E_NOT_LVALUE, "cannot access a field of a temporary struct".to_string())
+                .with_label(base.span, "not stored in a variable".to_string())),
+            Types::Struct(_) => base,
+            _ => return Err(Diagnostic::error(E_MISMATCHED_TYPES, format!("no field `{}` on type `{}`", name.text, base.typ))
+                .with_label(name.span, "not a struct or a pointer to a struct".to_string()))
+        };
+        let layout = match &base.typ {
+            Types::Struct(s) => self.types.get(s),
+            _ => None
+        };
+        let field = match layout.and_then(|l| l.field(&name.text)) {
+            Some(field) => field.types.clone(),
+            None => return Err(Diagnostic::error(E_UNRESOLVED_NAME, format!("no field `{}` on type `{}`", name.text, base.typ))
+                .with_label(name.span, "unknown field".to_string()))
+        };
+        let span = base.span.to(name.span);
+        Ok(Node::new_field(base, name.text.clone(), field, span))
+    }
+
+    fn parse_primary(&mut self, typ: &Types) -> ParseResult<Node> {
         self.pos += 1;
         if let Some(x) = self.tokens.get(self.pos) {
             match x {
                 Token { kind: TokenKind::Int, .. } => {
-                    match x.span.parse() {
-                        Ok(n) => Ok(Node::new_int(n, match typ {
-                            Types::Int(i) => i,
-                            _ => todo!()
-                        })),
-                        Err(_) => Err(format!("{}", "Missing Expr".to_string()))
+                    let typ = match typ {
+                        Types::Int(i) => *i,
+                        Types::Pointer(_) => return Err(self.error(E_MISMATCHED_TYPES, "mismatched types".to_string(), &format!("expected `{}`, found integer", typ))
+                            .with_note("integers do not convert to pointers, `null` is the null pointer".to_string())),
+                        _ => return Err(self.error(E_UNSUPPORTED_TYPE, "integer literal used as a non integer value".to_string(), "expected integer type"))
+                    };
+                    let (value, suffix) = int_literal(&x.text);
+                    match value {
+                        Some(n) => Ok(Node::new_int(n, suffix.unwrap_or(typ), suffix, x.span)),
+                        None => Err(self.error(E_LITERAL_OUT_OF_RANGE, format!("integer literal `{}` is too large", x.text), "literal out of range"))
+                    }
+                },
+                Token { kind: TokenKind::Char, .. } => {
+                    let value = unescape(&x.text[1..x.text.len() - 1]).first().copied().unwrap_or(0);
+                    match typ {
+                        Types::Int(i) => Ok(Node::new_int(value as i64, *i, None, x.span)),
+                        _ => Err(self.error(E_UNSUPPORTED_TYPE, "char literal used as a non integer value".to_string(), "expected integer type"))
                     }
                 },
-                _ => Err(format!("Unexpected Token {}", x.span))
+                Token { kind: TokenKind::Str, .. } => {
+                    // a string evaluates to the address of its bytes, a `*u8`
+                    // unless the context asks for a plain `u32`
+                    let typ = match typ {
+                        Types::Int(IntType::Uint32) => typ.clone(),
+                        _ => Types::Pointer(Box::new(Types::Int(IntType::Uint8)))
+                    };
+                    self.strings.push(unescape(&x.text[1..x.text.len() - 1]));
+                    Ok(Node { typ, ..Node::new_string(self.strings.len() - 1, x.span) })
+                },
+                // untyped `null` takes the pointer type of its context
+                Token { kind: TokenKind::Null, .. } => match typ {
+                    Types::Pointer(_) => Ok(Node::new_null(typ.clone(), x.span)),
+                    _ => Ok(Node::new_null(Types::Pointer(Box::new(Types::Int(IntType::Uint0))), x.span))
+                },
+                Token { kind: TokenKind::Identifier, .. } => {
+                    if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Lparen) {
+                        let call = self.parse_call(x)?;
+                        if call.typ == Types::Int(IntType::Uint0) {
+                            return Err(Diagnostic::error(E_MISMATCHED_TYPES, "mismatched types".to_string())
+                                .with_label(call.span, "this call returns nothing (`u0`)".to_string()));
+                        }
+                        return Ok(call);
+                    }
+                    // a struct name not shadowed by a variable starts a literal
+                    let literal = self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Lbrace)
+                        && self.types.get(&x.text).is_some()
+                        && !self.locals.iter().any(|b| b.name == x.text);
+                    if literal {
+                        return self.parse_struct_literal(x);
+                    }
+                    let typ = self.lookup(x)?.typ.clone();
+                    Ok(Node { typ, ..Node::new_identifier(x.text.clone(), x.span) })
+                },
+                Token { kind: TokenKind::Lbracket, .. } => self.parse_array(typ),
+                Token { kind: TokenKind::Lparen, .. } => {
+                    let inner = self.parse_binary(typ, 0)?;
+                    self.pos += 1;
+                    match self.tokens.get(self.pos) {
+                        Some(close) if close.kind == TokenKind::Rparen => Ok(inner),
+                        _ => Err(self.unclosed(x.span, "("))
+                    }
+                },
+                // already reported by the lexer, stand in a zero so parsing goes on
+                Token { kind: TokenKind::Error, .. } => Ok(Node::new_int(0, int_type(typ), None, x.span)),
+                _ => {
+                    // `1 + ;` points at the operator that lost its operand
+                    let prev = &self.tokens[self.pos - 1];
+                    if BinaryOp::from_token(prev.kind).is_some() || UnaryOp::from_token(prev.kind).is_some() {
+                        return Err(self.error(E_EXPECTED_TOKEN, format!("expected expression after `{}`, found {}", prev.text, describe(x)), "expected expression")
+                            .with_secondary(prev.span, "missing operand for this operator".to_string()));
+                    }
+                    Err(self.error(E_UNEXPECTED_TOKEN, format!("expected expression, found {}", describe(x)), "expected expression"))
+                }
             }
         } else {
-            Err(format!("{}", "Missing Token".to_string()))
+            Err(self.error(E_EXPECTED_TOKEN, "expected expression".to_string(), "expected expression"))
+        }
+    }
+
+    // `[a, b, c]` or `[value; count]`, the elements are typed by the array
+    // the context expects
+    fn parse_array(&mut self, typ: &Types) -> ParseResult<Node> {
+        let open = self.span();
+        let element = match typ {
+            Types::Array(element, _) => (**element).clone(),
+            _ => return Err(self.error(E_MISMATCHED_TYPES, "mismatched types".to_string(), &format!("expected `{}`, found an array", typ)))
+        };
+        let first = self.parse_expr(&element)?;
+        if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Semi) {
+            self.pos += 1;
+            let count = self.parse_length()?;
+            self.expect(TokenKind::Rbracket, "]")?;
+            let typ = Types::Array(Box::new(element), count);
+            return Ok(Node::new_array(NodeKind::ArrayRepeat(Box::new(first), count), typ, open.to(self.span())));
+        }
+        let mut values = vec![first];
+        loop {
+            self.pos += 1;
+            match self.tokens.get(self.pos) {
+                Some(t) if t.kind == TokenKind::Rbracket => break,
+                Some(t) if t.kind == TokenKind::Comma => values.push(self.parse_expr(&element)?),
+                Some(t) if !matches!(t.kind, TokenKind::Semi | TokenKind::Rbrace | TokenKind::Eof) => {
+                    return Err(self.error(E_EXPECTED_TOKEN, format!("expected `,` or `]`, found {}", describe(t)), "expected `,` or `]`")
+                        .with_secondary(open, "array starts here".to_string()))
+                }
+                _ => return Err(self.unclosed(open, "["))
+            }
+        }
+        let typ = Types::Array(Box::new(element), values.len() as u32);
+        Ok(Node::new_array(NodeKind::ArrayLit(values), typ, open.to(self.span())))
+    }
+
+    // `Name { a: value, b: value }`, every field is set exactly once and in
+    // any order, field errors are reported and the literal is still parsed to
+    // its `}` so the enclosing block stays intact
+    fn parse_struct_literal(&mut self, name: &Token) -> ParseResult<Node> {
+        self.pos += 1;
+        let open = self.span();
+        let layout = match self.types.get(&name.text) {
+            Some(layout) => layout.clone(),
+            None => return Err(Diagnostic::error(E_UNKNOWN_TYPE, format!("cannot find struct `{}`", name.text))
+                .with_label(name.span, "not a struct".to_string()))
+        };
+        let mut values: Vec<(String, Node)> = Vec::new();
+        let mut set: Vec<Span> = Vec::new();
+        loop {
+            self.pos += 1;
+            let token = match self.tokens.get(self.pos) {
+                Some(t) if t.kind == TokenKind::Rbrace => break,
+                Some(t) if t.kind == TokenKind::Identifier => t,
+                Some(t) if matches!(t.kind, TokenKind::Semi | TokenKind::Eof) => return Err(self.unclosed(open, "{")),
+                Some(t) => return Err(self.error(E_EXPECTED_TOKEN, format!("expected field name, found {}", describe(t)), "expected a field name")),
+                None => return Err(self.unclosed(open, "{"))
+            };
+            let field = layout.field(&token.text);
+            let error = match field {
+                None => Some(Diagnostic::error(E_UNRESOLVED_NAME, format!("struct `{}` has no field named `{}`", layout.name, token.text))
+                    .with_label(token.span, "unknown field".to_string())),
+                Some(field) => values.iter().position(|(f, _)| *f == field.name).map(|n| {
+                    Diagnostic::error(E_DUPLICATE_NAME, format!("field `{}` specified more than once", field.name))
+                        .with_label(token.span, "used more than once".to_string())
+                        .with_secondary(set[n], "first use of the field".to_string())
+                })
+            };
+            self.expect(TokenKind::Colon, ":")?;
+            let hint = field.map_or(Types::Int(IntType::Int32), |f| f.types.clone());
+            let value = self.parse_expr(&hint)?;
+            match (error, field) {
+                (Some(e), _) => self.report(e),
+                (None, Some(field)) => {
+                    values.push((field.name.clone(), value));
+                    set.push(token.span);
+                },
+                (None, None) => {}
+            }
+            match self.tokens.get(self.pos + 1) {
+                Some(t) if t.kind == TokenKind::Comma => self.pos += 1,
+                Some(t) if t.kind == TokenKind::Rbrace => {},
+                Some(t) if matches!(t.kind, TokenKind::Semi | TokenKind::Eof) => {
+                    self.pos += 1;
+                    return Err(self.unclosed(open, "{"));
+                },
+                Some(t) => {
+                    self.pos += 1;
+                    return Err(self.error(E_EXPECTED_TOKEN, format!("expected `,` or `}}`, found {}", describe(t)), "expected `,` or `}`")
+                        .with_secondary(open, "struct literal starts here".to_string()));
+                },
+                None => return Err(self.unclosed(open, "{"))
+            }
+        }
+        let span = name.span.to(self.span());
+        let missing: Vec<String> = layout.fields.iter()
+            .filter(|f| !values.iter().any(|(name, _)| *name == f.name))
+            .map(|f| format!("`{}`", f.name))
+            .collect();
+        if !missing.is_empty() {
+            let plural = if missing.len() > 1 { "s" } else { "" };
+            let e = Diagnostic::error(E_MISSING_FIELD, format!("missing field{} {} in initializer of `{}`", plural, missing.join(", "), layout.name))
+                .with_label(span, format!("missing {}", missing.join(", ")));
+            self.report(e);
+        }
+        Ok(Node::new_array(NodeKind::StructLit(values), Types::Struct(layout.name.clone()), span))
+    }
+
+    // length of an array type or of `[value; count]`, a positive integer constant
+    fn parse_length(&mut self) -> ParseResult<u32> {
+        self.pos += 1;
+        match self.tokens.get(self.pos) {
+            Some(t) if t.kind == TokenKind::Int => match int_literal(&t.text).0 {
+                Some(n) if n > 0 && n <= u32::MAX as i64 => Ok(n as u32),
+                _ => Err(self.error(E_LITERAL_OUT_OF_RANGE, format!("invalid array length `{}`", t.text), "the length has to be at least 1"))
+            },
+            Some(t) => Err(self.error(E_EXPECTED_TOKEN, format!("expected array length, found {}", describe(t)), "expected an integer constant")),
+            None => Err(self.error(E_EXPECTED_TOKEN, "expected array length".to_string(), "expected an integer constant"))
+        }
+    }
+
+    // `name(a, b)`, every argument is parsed like a value of its parameter's
+    // type and the call has the callee's return type
+    fn parse_call(&mut self, name: &Token) -> ParseResult<Node> {
+        let callee = match self.functions.iter().find(|f| f.name == name.text) {
+            Some(callee) => callee.clone(),
+            None => return Err(Diagnostic::error(E_UNRESOLVED_NAME, format!("cannot find function `{}` in this unit", name.text))
+                .with_label(name.span, "not found in this unit".to_string()))
+        };
+        self.pos += 1;
+        let open = self.span();
+        let mut args = Vec::new();
+        loop {
+            if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Rparen) {
+                self.pos += 1;
+                break;
+            }
+            // surplus arguments are still parsed so the count can be reported
+            let arg = match callee.params.get(args.len()) {
+                Some(param) => self.parse_expr(&param.types)?,
+                None => self.parse_binary(&Types::Int(IntType::Int32), 0)?
+            };
+            args.push(arg);
+            self.pos += 1;
+            match self.tokens.get(self.pos) {
+                Some(t) if t.kind == TokenKind::Comma => continue,
+                Some(t) if t.kind == TokenKind::Rparen => break,
+                Some(t) if !matches!(t.kind, TokenKind::Semi | TokenKind::Rbrace | TokenKind::Eof) => {
+                    return Err(self.error(E_EXPECTED_TOKEN, format!("expected `,` or `)`, found {}", describe(t)), "expected `,` or `)`")
+                        .with_secondary(open, "call arguments start here".to_string()))
+                }
+                _ => return Err(self.unclosed(open, "("))
+            }
+        }
+        let span = name.span.to(self.span());
+        if args.len() != callee.params.len() {
+            let plural = if callee.params.len() == 1 { "" } else { "s" };
+            return Err(Diagnostic::error(E_ARGUMENT_COUNT, format!("function `{}` takes {} argument{} but {} were supplied", name.text, callee.params.len(), plural, args.len()))
+                .with_label(span, format!("expected {} argument{}", callee.params.len(), plural))
+                .with_secondary(callee.span, format!("`{}` is defined here", name.text)));
+        }
+        Ok(Node::new_call(name.text.clone(), args, callee.returns, span))
+    }
+
+    // the next token has to be `kind`
+    fn expect(&mut self, kind: TokenKind, text: &str) -> ParseResult<()> {
+        self.pos += 1;
+        match self.tokens.get(self.pos) {
+            Some(t) if t.kind == kind => Ok(()),
+            Some(t) => Err(self.error(E_EXPECTED_TOKEN, format!("expected `{}`, found {}", text, describe(t)), &format!("expected `{}`", text))),
+            None => Err(self.error(E_EXPECTED_TOKEN, format!("expected `{}`", text), &format!("expected `{}`", text)))
+        }
+    }
+
+    // innermost declaration of the name in `token`
+    fn lookup(&self, token: &Token) -> ParseResult<&Binding> {
+        match self.locals.iter().rev().find(|b| b.name == token.text) {
+            Some(binding) => Ok(binding),
+            None => Err(Diagnostic::error(E_UNRESOLVED_NAME, format!("cannot find value `{}` in this scope", token.text))
+                .with_label(token.span, "not found in this scope".to_string()))
+        }
+    }
+
+    // arithmetic operands meet at the wider type, an unsuffixed literal
+    // takes the type of the other side
+    fn unify(&self, lhs: &mut Node, rhs: &mut Node) -> IntType {
+        let (l, r) = (int_type(&lhs.typ), int_type(&rhs.typ));
+        match (lhs.is_flexible(), rhs.is_flexible()) {
+            (true, false) => {
+                lhs.typ = rhs.typ.clone();
+                r
+            }
+            (false, true) => {
+                rhs.typ = lhs.typ.clone();
+                l
+            }
+            _ => common_type(l, r)
         }
     }
 
-    pub fn consume_semi(&mut self) -> Result<(), String> {
+    pub fn consume_semi(&mut self) -> ParseResult<()> {
         self.pos += 1;
         match self.tokens.get(self.pos) {
-            Some(cons) if cons.kind != TokenKind::Semi => Err(format!("Expected ; but found {}", cons.span)),
-            None => Err("Expected ; but there is nothing".to_string()),
+            Some(cons) if cons.kind != TokenKind::Semi => {
+                // point right behind the previous token, where the `;` is missing
+                let prev = self.tokens[self.pos - 1].span;
+                let missing = Span::new(prev.file, prev.end, prev.end);
+                Err(Diagnostic::error(E_EXPECTED_TOKEN, format!("expected `;`, found {}", describe(cons)))
+                    .with_label(missing, "expected `;`".to_string())
+                    .with_secondary(cons.span, "unexpected token".to_string())
+                    .with_suggestion(missing, ";".to_string(), "add `;` here".to_string()))
+            },
+            None => Err(self.error(E_EXPECTED_TOKEN, "expected `;`".to_string(), "expected `;`")),
             _ => Ok(())
         }
     }
+
+    // skips tokens until one of `kinds` (or Eof) is the current token
+    fn recover(&mut self, kinds: &[TokenKind]) {
+        while let Some(token) = self.tokens.get(self.pos) {
+            if token.kind == TokenKind::Eof || kinds.contains(&token.kind) {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    // location of the current token, past the end it sticks to the last token (Eof)
+    fn span(&self) -> Span {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(token) => token.span,
+            None => Span::default()
+        }
+    }
+
+    // errors pointing at an Error token were already reported by the lexer
+    fn report(&mut self, diagnostic: Diagnostic) {
+        let lexed = diagnostic.labels.iter().any(|label| {
+            self.tokens.iter().any(|t| t.kind == TokenKind::Error && (t.span.start..t.span.end).contains(&label.span.start))
+        });
+        if !lexed {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn error(&self, code: &'static str, message: String, label: &str) -> Diagnostic {
+        Diagnostic::error(code, message).with_label(self.span(), label.to_string())
+    }
+
+    fn unclosed(&self, open: Span, delimiter: &str) -> Diagnostic {
+        // a brace goes on its own line, a parenthesis right where it is missing
+        let (closing, replacement) = match delimiter {
+            "(" => (")", ")"),
+            "[" => ("]", "]"),
+            _ => ("}", "}\n")
+        };
+        let at = Span::new(open.file, self.span().start, self.span().start);
+        self.error(E_UNCLOSED_DELIMITER, format!("unclosed delimiter `{}`", delimiter), "expected closing delimiter")
+            .with_secondary(open, "unclosed delimiter".to_string())
+            .with_suggestion(at, replacement.to_string(), format!("close it with `{}`", closing))
+    }
 }
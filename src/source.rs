@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+
+pub type FileId = usize;
+
+// byte range `start..end` inside the file `file`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Self { file, start, end }
+    }
+
+    // smallest span covering both `self` and `other`
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.file, self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// 1-based line and column, columns count characters rather than bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    line_starts: Vec<usize>
+}
+
+impl SourceFile {
+    pub fn new(name: String, text: String) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in text.bytes().enumerate() {
+            if c == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        Self { name, text, line_starts }
+    }
+
+    // 0-based index of the line containing byte `offset`
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1
+        }
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        let line = self.line_index(offset);
+        let start = self.line_starts[line];
+        let offset = offset.min(self.text.len());
+        let column = self.text.get(start..offset).map_or(offset - start, |s| s.chars().count()) + 1;
+        Location { line: line + 1, column }
+    }
+
+    // text of the 0-based line `line` without its line terminator
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: String, text: String) -> FileId {
+        let id = self.files.len();
+        self.files.push(SourceFile::new(name, text));
+        id
+    }
+
+    // `None` for ids that were not handed out by this map
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id)
+    }
+
+    // `name:line:column` of the start of `span`
+    pub fn describe(&self, span: Span) -> Option<String> {
+        self.get(span.file).map(|file| format!("{}:{}", file.name, file.location(span.start)))
+    }
+}
@@ -0,0 +1,177 @@
+use suco::{Emit, Options};
+
+fn asm(source: &str) -> String {
+    match suco::compile_str(source, &Options::new("test.su", Emit::Asm)) {
+        Ok(artifact) => String::from_utf8(artifact.bytes).unwrap(),
+        Err(diagnostics) => panic!("{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>())
+    }
+}
+
+fn errors(source: &str) -> Vec<&'static str> {
+    match suco::compile_str(source, &Options::new("test.su", Emit::Asm)) {
+        Ok(_) => Vec::new(),
+        Err(diagnostics) => diagnostics.iter().map(|d| d.code).collect()
+    }
+}
+
+#[test]
+fn output_is_32_bit_code_with_exported_functions() {
+    let out = asm("defun f() -> i32 { return 0; }\ndefun main() -> i32 { return 0; }");
+    assert!(out.starts_with("bits 32\nsection .text\nglobal f\nglobal main\nf:\n"), "{}", out);
+}
+
+#[test]
+fn strings_are_emitted_as_read_only_bytes() {
+    let out = asm("defun f() -> i32 { let s: *u8 = \"a\\n\\x41\\\"\\\\\"; return 0; }");
+    assert!(out.contains("\tmov dword [ebp-4], str.0\n"), "{}", out);
+    assert!(out.ends_with("section .rodata\nstr.0:\n\tdb 97, 10, 65, 34, 92, 0\n"), "{}", out);
+}
+
+#[test]
+fn signed_operand_is_converted_before_unsigned_division() {
+    let out = asm("defun g(a: i8, b: u8) -> u8 { return a / b; }");
+    assert!(out.contains("\tpop eax\n\tmovzx eax, al\n\txor edx, edx\n\tdiv ecx\n"), "{}", out);
+}
+
+#[test]
+fn narrower_operand_is_converted_to_the_wider_signed_type() {
+    let out = asm("defun g(a: i16, b: i8) -> i16 { return a / b; }");
+    assert!(out.contains("\tmovsx ecx, cx\n\tcdq\n\tidiv ecx\n"), "{}", out);
+}
+
+#[test]
+fn same_width_operands_are_not_converted() {
+    let out = asm("defun g(a: u8, b: u8) -> u8 { return a % b; }");
+    assert!(out.contains("\tpop eax\n\txor edx, edx\n\tdiv ecx\n"), "{}", out);
+}
+
+#[test]
+fn shift_keeps_the_left_operand_type() {
+    let out = asm("defun g(a: i8) -> i8 { return a >> 1; }");
+    assert!(out.contains("\tmov ecx, 1\n\tsar eax, cl\n\tmovsx eax, al\n"), "{}", out);
+}
+
+#[test]
+fn mixed_widths_compile_without_errors() {
+    assert!(errors("defun f(a: u8) -> i32 { return a + 100; }").is_empty());
+}
+
+#[test]
+fn compound_assignment_reads_operates_and_stores() {
+    let out = asm("defun f() -> i32 { let mut x: i16 = 1; x -= 2; return x; }");
+    assert!(out.contains("\tmovsx eax, word [ebp-2]\n\tmov ecx, 2\n\tsub eax, ecx\n\tmovsx eax, ax\n\tmov word [ebp-2], ax\n"), "{}", out);
+}
+
+#[test]
+fn comparison_converts_to_the_common_type() {
+    let out = asm("defun g(a: i8, b: u16) -> i32 { if a < b { return 1; } return 0; }");
+    assert!(out.contains("\tmovzx eax, ax\n\tcmp eax, ecx\n"), "{}", out);
+}
+
+#[test]
+fn if_else_chains_get_distinct_labels() {
+    let out = asm("defun f(a: i32) -> i32 { if a == 1 { return 1; } else if a == 2 { return 2; } else { return 3; } return 0; }");
+    assert!(out.contains("\tjne .else1\n") && out.contains("\tjmp .endif2\n.else1:\n"), "{}", out);
+    assert!(out.contains("\tjne .else3\n") && out.contains("\tjmp .endif4\n.else3:\n"), "{}", out);
+    assert!(out.contains(".endif4:\n.endif2:\n"), "{}", out);
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    let out = asm("defun f(a: i32, b: i32) -> i32 { if a > 0 && b > 0 { return 1; } return 0; }");
+    assert!(out.contains("\tcmp eax, ecx\n\tjle .else1\n\tmov eax, dword [ebp+12]\n\tmov ecx, 0\n\tcmp eax, ecx\n\tjle .else1\n"), "{}", out);
+    let out = asm("defun f(a: i32, b: i32) -> i32 { if a > 0 || b > 0 { return 1; } return 0; }");
+    assert!(out.contains("\tcmp eax, ecx\n\tjg .skip2\n\tmov eax, dword [ebp+12]\n\tmov ecx, 0\n\tcmp eax, ecx\n\tjle .else1\n.skip2:\n"), "{}", out);
+}
+
+#[test]
+fn for_bound_has_to_fit_the_counter() {
+    assert_eq!(errors("defun f() -> i32 { for i: u8 in 0..300 { } return 0; }"), vec!["E0005"]);
+    assert_eq!(errors("defun f(n: u16) -> i32 { for i: u8 in 0..n { } return 0; }"), vec!["E0012"]);
+    assert!(errors("defun f(n: u8) -> i32 { for i: u16 in 0..n { } for j in 0..n { } return 0; }").is_empty());
+}
+
+#[test]
+fn dense_switch_uses_a_jump_table() {
+    let out = asm("defun f(x: i32) -> i32 { switch x { case 1 => { return 10; } case 2 => { return 20; } case 3 => { return 30; } case 4 => { return 40; } } return 0; }");
+    assert!(out.contains("\tsub eax, 1\n\tcmp eax, 3\n\tja .send1\n\tjmp [switch."), "{}", out);
+    assert!(out.contains("align 4\nswitch."), "{}", out);
+}
+
+#[test]
+fn small_or_sparse_switch_uses_compares() {
+    let three = asm("defun f(x: i32) -> i32 { switch x { case 1 => { return 10; } case 2 => { return 20; } case 3 => { return 30; } } return 0; }");
+    assert!(three.contains("\tcmp eax, 1\n\tje .case") && !three.contains("jmp [switch."), "{}", three);
+    // a span of 12 values for 4 arms is just too wide
+    let sparse = asm("defun f(x: i32) -> i32 { switch x { case 0 => { return 1; } case 1 => { return 2; } case 2 => { return 3; } case 12 => { return 4; } } return 0; }");
+    assert!(!sparse.contains("jmp [switch."), "{}", sparse);
+    let dense = asm("defun f(x: i32) -> i32 { switch x { case 0 => { return 1; } case 1 => { return 2; } case 2 => { return 3; } case 11 => { return 4; } } return 0; }");
+    assert!(dense.contains("jmp [switch."), "{}", dense);
+}
+
+#[test]
+fn jump_table_is_rebased_on_a_negative_minimum() {
+    let out = asm("defun f(x: i32) -> i32 { switch x { case -1 => { return 1; } case 0 => { return 2; } case 1, 2 => { return 3; } } return 0; }");
+    assert!(out.contains("\tsub eax, -1\n\tcmp eax, 3\n"), "{}", out);
+}
+
+#[test]
+fn call_pushes_arguments_right_to_left_and_cleans_the_stack() {
+    let out = asm("defun f(a: i32) -> i32 { return g(a, 2); }\ndefun g(a: i32, b: u8) -> u8 { return b; }");
+    assert!(out.contains("\tpush dword 2\n\tmov eax, dword [ebp+8]\n\tpush eax\n\tcall g\n\tadd esp, 8\n\tmovzx eax, al\n"), "{}", out);
+}
+
+#[test]
+fn pointer_arithmetic_scales_by_the_element_size() {
+    let out = asm("defun f(p: *i32) -> *i32 { return p + 3; }");
+    assert!(out.contains("\tmov ecx, 3\n\timul ecx, ecx, 4\n\tadd eax, ecx\n"), "{}", out);
+    let out = asm("defun f(p: *i32) -> *i32 { return p - 1; }");
+    assert!(out.contains("\timul ecx, ecx, 4\n\tsub eax, ecx\n"), "{}", out);
+}
+
+#[test]
+fn pointer_difference_counts_elements() {
+    let out = asm("defun f(p: *i16, q: *i16) -> i32 { return p - q; }");
+    assert!(out.contains("\tsub eax, ecx\n\tsar eax, 1\n"), "{}", out);
+}
+
+#[test]
+fn null_is_a_zero_pointer() {
+    let out = asm("defun f() -> i32 { let p: *u8 = null; if p == null { return 1; } return 0; }");
+    assert!(out.contains("\tmov dword [ebp-4], 0\n"), "{}", out);
+    assert_eq!(errors("defun f() -> i32 { let p: *u8 = 0; return 0; }"), vec!["E0012"]);
+}
+
+#[test]
+fn array_index_scales_by_the_element_size() {
+    let out = asm("defun f(i: i32) -> i32 { let a: [i16; 4] = [0; 4]; return a[i]; }");
+    assert!(out.contains("\tlea eax, [eax+ecx*2]\n\tmovsx eax, word [eax]\n"), "{}", out);
+}
+
+#[test]
+fn repeated_array_literal_is_filled_in_a_loop() {
+    let out = asm("defun f() -> i32 { let a: [i16; 4] = [7; 4]; return 0; }");
+    assert!(out.contains("\tlea edx, [ebp-8]\n\tmov ecx, 4\n.fill1:\n\tmov word [edx], 7\n\tadd edx, 2\n\tsub ecx, 1\n\tjnz .fill1\n"), "{}", out);
+}
+
+#[test]
+fn arrays_are_copied_by_value() {
+    let out = asm("defun f() -> i32 { let a: [u8; 3] = [1, 2, 3]; let b: [u8; 3] = a; return 0; }");
+    assert!(out.contains("\tmov byte [ebp-3], 1\n\tmov byte [ebp-2], 2\n\tmov byte [ebp-1], 3\n"), "{}", out);
+    assert!(out.contains("\tmov cx, [eax]\n\tmov word [edx], cx\n\tmov cl, [eax+2]\n\tmov byte [edx+2], cl\n"), "{}", out);
+    let out = asm("defun f() -> i32 { let a: [i32; 100] = [7; 100]; let b: [i32; 100] = a; return 0; }");
+    assert!(out.contains("\tmov ecx, 400\n\trep movsb\n"), "{}", out);
+}
+
+#[test]
+fn arrow_dereferences_the_pointer_and_adds_the_field_offset() {
+    let out = asm("struct S { a: u8, b: i32 }\ndefun f(p: *S) -> i32 { return p->b; }");
+    assert!(out.contains("\tmov eax, dword [ebp+8]\n\tadd eax, 4\n\tmov eax, dword [eax]\n"), "{}", out);
+}
+
+#[test]
+fn dot_on_a_pointer_derefs_like_arrow() {
+    let arrow = asm("struct S { a: u8, b: i32 }\ndefun f(p: *S) -> i32 { return p->b; }");
+    let dot = asm("struct S { a: u8, b: i32 }\ndefun f(p: *S) -> i32 { return p.b; }");
+    assert_eq!(arrow, dot);
+}
@@ -0,0 +1,28 @@
+use suco::diagnostics::NO_CODE;
+use suco::{Diagnostic, Emit, Options, SourceMap, Span};
+
+#[test]
+fn driver_errors_have_a_null_code_and_the_full_schema() {
+    let json = Diagnostic::error(NO_CODE, "cannot open x.su".to_string()).to_json(&SourceMap::new());
+    assert!(json.starts_with("{\"severity\":\"error\",\"code\":null,\"message\":\"cannot open x.su\""), "{}", json);
+    assert!(json.ends_with("\"rendered\":\"error: cannot open x.su\\n\"}"), "{}", json);
+}
+
+#[test]
+fn compile_errors_render_against_the_compiled_source() {
+    let source = "defun main() -> i32 { return x; }";
+    let options = Options::new("test.su", Emit::Asm);
+    let diagnostics = suco::compile_str(source, &options).unwrap_err();
+    let rendered = suco::render(source, &options, &diagnostics);
+    assert!(rendered.contains("--> test.su:1:30\n"), "{}", rendered);
+    assert!(rendered.contains("1 | defun main() -> i32 { return x; }\n"), "{}", rendered);
+}
+
+#[test]
+fn spans_of_unknown_files_render_without_source_lines() {
+    let sources = SourceMap::new();
+    assert!(sources.get(3).is_none());
+    let diagnostic = Diagnostic::error(NO_CODE, "lost".to_string()).with_label(Span::new(3, 0, 1), "here".to_string());
+    assert_eq!(diagnostic.render(&sources), "error: lost\n");
+    assert!(diagnostic.to_json(&sources).contains("\"file\":null,\"span\":null,\"labels\":[{\"span\":null,"));
+}
@@ -0,0 +1,63 @@
+use suco::TokenKind;
+
+fn kinds(source: &str) -> Vec<TokenKind> {
+    suco::tokenize(source).unwrap().iter().map(|t| t.kind).collect()
+}
+
+#[test]
+fn block_comments_nest() {
+    let tokens = suco::tokenize("/* a /* b */ c */ x").unwrap();
+    assert_eq!(tokens[0].kind, TokenKind::Identifier);
+    assert_eq!(tokens[0].span.start, 18);
+}
+
+#[test]
+fn unterminated_block_comment_is_reported_where_it_starts() {
+    let diagnostics = suco::tokenize("x /* a /* b */").unwrap_err();
+    let diagnostic = diagnostics.iter().next().unwrap();
+    assert_eq!(diagnostic.code, "E0010");
+    assert_eq!(diagnostic.labels[0].span.start, 2);
+}
+
+#[test]
+fn unknown_escape_is_an_error() {
+    let diagnostics = suco::tokenize(r#""a\q""#).unwrap_err();
+    assert_eq!(diagnostics.iter().next().unwrap().code, "E0014");
+}
+
+#[test]
+fn operators_are_lexed_by_maximal_munch() {
+    assert_eq!(kinds("a >>= b"), vec![TokenKind::Identifier, TokenKind::ShrEqual, TokenKind::Identifier, TokenKind::Eof]);
+    assert_eq!(kinds("p->x"), vec![TokenKind::Identifier, TokenKind::Arrow, TokenKind::Identifier, TokenKind::Eof]);
+    assert_eq!(kinds("a > >= b"), vec![TokenKind::Identifier, TokenKind::Greater, TokenKind::GreaterEqual, TokenKind::Identifier, TokenKind::Eof]);
+}
+
+#[test]
+fn identifiers_accept_underscores_and_unicode_xid() {
+    assert_eq!(kinds("_x1 größe"), vec![TokenKind::Identifier, TokenKind::Identifier, TokenKind::Eof]);
+}
+
+#[test]
+fn combining_marks_continue_an_identifier() {
+    let tokens = suco::tokenize("cafe\u{301}").unwrap();
+    assert_eq!(tokens[0].kind, TokenKind::Identifier);
+    assert_eq!(tokens[0].text, "cafe\u{301}");
+}
+
+#[test]
+fn combining_mark_cannot_start_an_identifier() {
+    assert!(suco::tokenize("\u{301}x").is_err());
+}
+
+#[test]
+fn lossless_tokens_give_back_the_source() {
+    let source = "/// doc\ndefun main() -> i32 {  // trailing\n\t/* a /* nested */ b */ return 0x1_0u8 ; }\n\n";
+    let tokens = suco::tokenize_lossless(source).unwrap();
+    assert_eq!(suco::untokenize(&tokens), source);
+    assert!(tokens.iter().all(|t| !t.is_trivia()));
+}
+
+#[test]
+fn range_dots_are_one_token() {
+    assert_eq!(kinds("0..n"), vec![TokenKind::Int, TokenKind::DotDot, TokenKind::Identifier, TokenKind::Eof]);
+}
@@ -0,0 +1,197 @@
+use suco::parser::{IntType, NodeKind, Types};
+use suco::{Diagnostics, Node, Unit};
+
+fn parse(source: &str) -> Result<Unit, Diagnostics> {
+    let tokens = suco::tokenize(source)?;
+    suco::parse(&tokens)
+}
+
+// the value of the first `return` in the first function
+fn returned(source: &str) -> Node {
+    let unit = parse(source).unwrap_or_else(|d| panic!("{:?}", d.iter().map(|e| &e.message).collect::<Vec<_>>()));
+    let body = match &unit.items[0].kind {
+        NodeKind::Function(_, _, _, body) => body,
+        kind => panic!("not a function: {:?}", kind)
+    };
+    let statements = match &body.kind {
+        NodeKind::Scope(statements) => statements,
+        kind => panic!("not a scope: {:?}", kind)
+    };
+    statements.iter().find_map(|s| match &s.kind {
+        NodeKind::Return(Some(value)) => Some((**value).clone()),
+        _ => None
+    }).expect("no return value")
+}
+
+// the expression tree with every binary operation in parentheses
+fn shape(node: &Node) -> String {
+    match &node.kind {
+        NodeKind::Binary(op, lhs, rhs) => format!("({} {} {})", shape(lhs), op, shape(rhs)),
+        NodeKind::Unary(op, operand) => format!("{}{}", op, shape(operand)),
+        NodeKind::NumberLit(n, _) => n.to_string(),
+        NodeKind::Identifier(name) => name.clone(),
+        kind => format!("{:?}", kind)
+    }
+}
+
+fn codes(source: &str) -> Vec<&'static str> {
+    match parse(source) {
+        Ok(_) => Vec::new(),
+        Err(diagnostics) => diagnostics.iter().map(|d| d.code).collect()
+    }
+}
+
+#[test]
+fn end_of_file_is_named_in_messages() {
+    let diagnostics = parse("defun main() -> i32 { return 1 +").unwrap_err();
+    let message = &diagnostics.iter().next().unwrap().message;
+    assert_eq!(message, "expected expression after `+`, found end of file");
+    assert!(diagnostics.iter().all(|d| !d.message.contains('\0')));
+}
+
+#[test]
+fn multiplication_binds_tighter_than_addition() {
+    let expr = returned("defun f(a: i32, b: i32, c: i32) -> i32 { return a + b * c; }");
+    assert_eq!(shape(&expr), "(a + (b * c))");
+    let expr = returned("defun f(a: i32, b: i32, c: i32) -> i32 { return a * b + c; }");
+    assert_eq!(shape(&expr), "((a * b) + c)");
+}
+
+#[test]
+fn comparison_binds_looser_than_arithmetic_and_tighter_than_logic() {
+    let expr = returned("defun f(a: i32, b: i32) -> i32 { return a + 1 < b && b != 0; }");
+    assert_eq!(shape(&expr), "(((a + 1) < b) && (b != 0))");
+}
+
+#[test]
+fn equal_precedence_associates_to_the_left() {
+    let expr = returned("defun f(a: i32, b: i32, c: i32) -> i32 { return a - b - c; }");
+    assert_eq!(shape(&expr), "((a - b) - c)");
+    let expr = returned("defun f(a: i32, b: i32, c: i32) -> i32 { return a / b * c; }");
+    assert_eq!(shape(&expr), "((a / b) * c)");
+}
+
+#[test]
+fn parentheses_override_precedence() {
+    let expr = returned("defun f(a: i32, b: i32, c: i32) -> i32 { return (a + b) * c; }");
+    assert_eq!(shape(&expr), "((a + b) * c)");
+}
+
+#[test]
+fn unsuffixed_literal_takes_the_type_of_the_other_operand() {
+    let expr = returned("defun f(a: u8) -> i32 { return a + 1; }");
+    assert_eq!(expr.typ, Types::Int(IntType::Uint8));
+    let expr = returned("defun f(a: u8) -> i32 { return a + 100; }");
+    assert_eq!(expr.typ, Types::Int(IntType::Uint8));
+}
+
+#[test]
+fn suffixed_literal_is_only_checked_when_alone() {
+    assert!(codes("defun f(a: u8) -> i32 { return a + 1u8; }").is_empty());
+    assert_eq!(codes("defun f() -> i32 { return 1u8; }"), vec!["E0012"]);
+}
+
+#[test]
+fn suffix_suggestion_replaces_the_suffix() {
+    let source = "defun f() -> i32 { return 0x10u16; }";
+    let diagnostics = parse(source).unwrap_err();
+    let suggestion = &diagnostics.iter().next().unwrap().suggestions[0];
+    assert_eq!(&source[suggestion.span.start..suggestion.span.end], "u16");
+    assert_eq!(suggestion.replacement, "i32");
+}
+
+#[test]
+fn no_suffix_suggestion_for_values_out_of_range() {
+    let diagnostics = parse("defun f() -> u8 { return -1i8; }").unwrap_err();
+    let diagnostic = diagnostics.iter().next().unwrap();
+    assert_eq!(diagnostic.code, "E0012");
+    assert!(diagnostic.suggestions.is_empty());
+    let diagnostics = parse("defun f() -> u8 { return 300u16; }").unwrap_err();
+    assert!(diagnostics.iter().next().unwrap().suggestions.is_empty());
+    let diagnostics = parse("defun f() -> i16 { return -1i8; }").unwrap_err();
+    assert_eq!(diagnostics.iter().next().unwrap().suggestions[0].replacement, "i16");
+}
+
+#[test]
+fn assigning_to_an_immutable_binding_is_an_error() {
+    let diagnostics = parse("defun f() -> i32 { let x: i32 = 1; x = 2; return x; }").unwrap_err();
+    let diagnostic = diagnostics.iter().next().unwrap();
+    assert_eq!(diagnostic.code, "E0016");
+    assert_eq!(diagnostic.suggestions[0].replacement, "mut ");
+    assert_eq!(codes("defun f(a: i32) -> i32 { a += 1; return a; }"), vec!["E0016"]);
+    assert_eq!(codes("defun f() -> i32 { let a: [i32; 2] = [1, 2]; a[0] = 3; return 0; }"), vec!["E0016"]);
+    assert!(codes("defun f() -> i32 { let mut x: i32 = 1; x = 2; x *= 3; return x; }").is_empty());
+}
+
+#[test]
+fn loop_counters_are_immutable_without_a_mut_suggestion() {
+    let diagnostics = parse("defun f() -> i32 { for i in 0..3 { i = 1; } return 0; }").unwrap_err();
+    let diagnostic = diagnostics.iter().next().unwrap();
+    assert_eq!(diagnostic.code, "E0016");
+    assert!(diagnostic.suggestions.is_empty());
+    assert_eq!(diagnostic.notes, vec!["loop counters are immutable"]);
+}
+
+#[test]
+fn break_and_continue_need_a_loop() {
+    assert_eq!(codes("defun f() -> i32 { break; return 0; }"), vec!["E0017"]);
+    assert_eq!(codes("defun f() -> i32 { while 1 { continue; } return 0; }"), Vec::<&str>::new());
+}
+
+#[test]
+fn calls_resolve_functions_defined_further_down() {
+    let expr = returned("defun f() -> i32 { return g(1); }\ndefun g(a: u8) -> u16 { return a; }");
+    assert_eq!(expr.typ, Types::Int(IntType::Uint16));
+    match &expr.kind {
+        NodeKind::Call(name, args) => {
+            assert_eq!(name, "g");
+            assert_eq!(args[0].typ, Types::Int(IntType::Uint8));
+        },
+        kind => panic!("not a call: {:?}", kind)
+    }
+}
+
+#[test]
+fn calls_are_checked_when_parsing() {
+    assert_eq!(codes("defun f() -> i32 { return h(); }"), vec!["E0015"]);
+    assert_eq!(codes("defun g(a: u8) -> i32 { return a; }\ndefun f() -> i32 { return g(1, 2); }"), vec!["E0019"]);
+    assert_eq!(codes("defun g(p: *u8) -> i32 { return 0; }\ndefun f(q: *i32) -> i32 { return g(q); }"), vec!["E0012"]);
+    assert_eq!(codes("defun g() -> u0 { return; }\ndefun f() -> i32 { return g(); }"), vec!["E0012"]);
+}
+
+#[test]
+fn second_definition_of_a_function_is_an_error() {
+    let source = "defun g() -> i32 { return 1; }\ndefun g() -> i32 { return 2; }";
+    let diagnostics = parse(source).unwrap_err();
+    let diagnostic = diagnostics.iter().next().unwrap();
+    assert_eq!(diagnostic.code, "E0020");
+    assert_eq!(diagnostic.labels[0].span.start, source.rfind('g').unwrap());
+}
+
+#[test]
+fn struct_fields_get_c_layout() {
+    let unit = parse("struct S { a: u8, b: u16, c: i32, d: u8 }").unwrap();
+    let layout = unit.types.get("S").unwrap();
+    let offsets: Vec<u32> = layout.fields.iter().map(|f| f.offset).collect();
+    assert_eq!(offsets, vec![0, 2, 4, 8]);
+    assert_eq!((layout.size, layout.align), (12, 4));
+}
+
+#[test]
+fn structs_can_point_to_each_other() {
+    let unit = parse("struct A { b: *B }\nstruct B { a: *A, x: u8 }").unwrap();
+    assert_eq!(unit.types.get("A").unwrap().size, 4);
+    assert_eq!(unit.types.get("B").unwrap().size, 8);
+}
+
+#[test]
+fn struct_cannot_contain_itself_by_value() {
+    assert_eq!(codes("struct A { x: i32, a: A }"), vec!["E0006"]);
+    assert_eq!(codes("struct A { b: B }\nstruct B { x: i32 }"), vec!["E0006"]);
+}
+
+#[test]
+fn functions_see_structs_declared_further_down() {
+    let expr = returned("defun f(p: *S) -> i32 { return p->x; }\nstruct S { x: i32 }");
+    assert_eq!(expr.typ, Types::Int(IntType::Int32));
+}